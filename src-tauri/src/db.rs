@@ -1,9 +1,13 @@
-﻿use std::path::PathBuf;
+﻿use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
 
-use chrono::Utc;
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveTime, Utc};
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, OptionalExtension};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, params_from_iter, ErrorCode, OptionalExtension, TransactionBehavior};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 use thiserror::Error;
@@ -17,43 +21,285 @@ pub enum DbError {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Pool(#[from] r2d2::Error),
+    #[error("database connections are busy, please try again: {0}")]
+    PoolExhausted(String),
     #[error(transparent)]
     Sqlite(#[from] rusqlite::Error),
     #[error("invalid input: {0}")]
     InvalidInput(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("policy violation: {0}")]
+    PolicyViolation(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
 }
 
 pub type Result<T> = std::result::Result<T, DbError>;
 
+const SETTING_DOCK_SIDE: &str = "dock_side";
+const SETTING_TOGGLE_SHORTCUT: &str = "toggle_shortcut";
+const DEFAULT_TOGGLE_SHORTCUT: &str = "Alt+Q";
+const SETTING_QUICK_ADD_SHORTCUT: &str = "quick_add_shortcut";
+const DEFAULT_QUICK_ADD_SHORTCUT: &str = "Alt+Shift+Q";
+const SETTING_PANEL_MODE: &str = "panel_mode";
+const SETTING_TASKBAR_OFFSET: &str = "taskbar_offset";
+pub const DEFAULT_TASKBAR_OFFSET: f64 = 48.0;
+const MAX_TASKBAR_OFFSET: f64 = 500.0;
+const SETTING_ACTIVE_TASK_LIMIT: &str = "active_task_limit";
+const DEFAULT_ACTIVE_TASK_LIMIT: i64 = 50;
+const SETTING_WINDOW_GEOMETRY_PREFIX: &str = "window_geometry:";
+const SETTING_LAST_VACUUM_AT: &str = "last_vacuum_at";
+const SETTING_DND_UNTIL: &str = "dnd_until";
+const SETTING_LOG_LEVEL: &str = "log_level";
+const SETTING_DAILY_CAP_PREFIX: &str = "daily_cap:";
+const DND_INDEFINITE: &str = "indefinite";
+
+const SCHEMA_VERSION: i64 = 2;
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5000);
+const POOL_RETRY_ATTEMPTS: u32 = 3;
+const POOL_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+const TASK_TYPES: [&str; 3] = ["once", "cycle", "long_term"];
+const TASK_STATUSES: [&str; 3] = ["active", "completed", "archived"];
+const REGENERATE_RULES: [&str; 4] = ["weekly", "monthly", "quarterly", "yearly"];
+const MAX_TASK_TARGET: i64 = 100_000;
+const MAX_NAME_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
+const FUZZY_MATCH_THRESHOLD: f64 = 0.6;
+const MAX_TAG_LENGTH: usize = 50;
+const MAX_PRIORITY: i64 = 5;
+const TREND_THRESHOLD: f64 = 0.1;
+const MAX_BATCH_SIZE: usize = 500;
+const MAX_QUERY_ROWS: usize = 1000;
+
+const DATA_DIR_ENV: &str = "TIMEMASTER_DATA_DIR";
+const DATA_DIR_POINTER: &str = "data_dir";
+const DATABASE_FILE: &str = "timemaster.db";
+const PROFILE_POINTER: &str = "profile";
+const DEFAULT_PROFILE: &str = "default";
+const MAX_PROFILE_NAME_LENGTH: usize = 32;
+
+const TASK_COLUMNS: &str = "id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at, color, snoozed_until, frozen_until, notes, parent_id, reminder_time, priority, regenerate_rule, (SELECT group_concat(tag, char(31)) FROM task_tags WHERE task_tags.task_id = tasks.id) AS tags";
+
 #[derive(Clone)]
 pub struct Db {
+    inner: Arc<RwLock<DbInner>>,
+}
+
+struct DbInner {
     pool: Pool<SqliteConnectionManager>,
+    path: Option<PathBuf>,
 }
 
 impl Db {
     pub fn init(handle: &AppHandle) -> Result<Self> {
-        let db_path = Self::database_path(handle)?;
+        Self::open(Self::database_path(handle)?)
+    }
+
+    fn new(pool: Pool<SqliteConnectionManager>, path: Option<PathBuf>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(DbInner { pool, path })),
+        }
+    }
+
+    fn pool(&self) -> Pool<SqliteConnectionManager> {
+        self.inner
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pool
+            .clone()
+    }
+
+    fn path(&self) -> Option<PathBuf> {
+        self.inner
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .path
+            .clone()
+    }
+
+    pub fn init_fallback() -> Result<Self> {
+        Self::open(std::env::temp_dir().join("timemaster").join(DATABASE_FILE))
+    }
+
+    fn open(db_path: PathBuf) -> Result<Self> {
         if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            create_storage_dir(parent)?;
         }
+        probe_writable(&db_path)?;
 
-        let manager = SqliteConnectionManager::file(db_path);
+        let manager = SqliteConnectionManager::file(&db_path)
+            .with_init(|conn| conn.busy_timeout(BUSY_TIMEOUT));
         let pool = Pool::builder().max_size(8).build(manager)?;
-        let db = Self { pool };
+        let db = Self::new(pool, Some(db_path));
         db.run_migrations()?;
+        let repaired = db.repair_consistency()?;
+        if repaired > 0 {
+            log::warn!("repaired {repaired} tasks with inconsistent progress or status");
+        }
         Ok(db)
     }
 
-    fn database_path(handle: &AppHandle) -> Result<PathBuf> {
+    // A private in-memory database lives only as long as its connection, so the
+    // pool holds exactly one connection that every caller shares and that is
+    // never closed for being idle or old.
+    pub fn init_in_memory() -> Result<Self> {
+        let manager = SqliteConnectionManager::memory();
+        let pool = Pool::builder()
+            .max_size(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .build(manager)?;
+        let db = Self::new(pool, None);
+        db.run_migrations()?;
+        Ok(db)
+    }
+
+    fn file_path(&self) -> Result<PathBuf> {
+        self.path()
+            .ok_or_else(|| DbError::InvalidInput("database is not backed by a file".into()))
+    }
+
+    fn default_storage_dir(handle: &AppHandle) -> Result<PathBuf> {
         let base = handle
             .path()
             .app_data_dir()
             .map_err(|err| DbError::AppDir(err.to_string()))?;
-        Ok(base.join("storage").join("timemaster.db"))
+        Ok(base.join("storage"))
+    }
+
+    fn database_path(handle: &AppHandle) -> Result<PathBuf> {
+        let file = profile_file(&Self::active_profile(handle)?);
+        if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            return Ok(PathBuf::from(dir).join(file));
+        }
+        let default = Self::default_storage_dir(handle)?;
+        match std::fs::read_to_string(default.join(DATA_DIR_POINTER)) {
+            Ok(dir) if !dir.trim().is_empty() => Ok(PathBuf::from(dir.trim()).join(file)),
+            _ => Ok(default.join(file)),
+        }
+    }
+
+    pub fn active_profile(handle: &AppHandle) -> Result<String> {
+        let pointer = Self::default_storage_dir(handle)?.join(PROFILE_POINTER);
+        Ok(std::fs::read_to_string(pointer)
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| validate_profile_name(name).is_ok())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string()))
+    }
+
+    // Commands hold a shared Db, so the pool is swapped in place; connections
+    // already checked out keep the previous file until they are dropped.
+    pub fn switch_profile(&self, handle: &AppHandle, name: &str) -> Result<PathBuf> {
+        let name = name.trim();
+        validate_profile_name(name)?;
+        let default_dir = Self::default_storage_dir(handle)?;
+        let pointer = default_dir.join(PROFILE_POINTER);
+        let previous = std::fs::read_to_string(&pointer).ok();
+        create_storage_dir(&default_dir)?;
+        if name == DEFAULT_PROFILE {
+            match std::fs::remove_file(&pointer) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        } else {
+            std::fs::write(&pointer, name.as_bytes())?;
+        }
+
+        let opened = Self::database_path(handle).and_then(Self::open);
+        let next = match opened {
+            Ok(next) => next,
+            Err(err) => {
+                let _ = match previous {
+                    Some(previous) => std::fs::write(&pointer, previous),
+                    None => std::fs::remove_file(&pointer),
+                };
+                return Err(err);
+            }
+        };
+        let path = next.file_path()?;
+        *self.inner.write().unwrap_or_else(PoisonError::into_inner) = DbInner {
+            pool: next.pool(),
+            path: Some(path.clone()),
+        };
+        log::info!("switched to profile {name} at {}", path.display());
+        Ok(path)
+    }
+
+    pub fn relocate(&self, handle: &AppHandle, dir: Option<&str>) -> Result<PathBuf> {
+        if std::env::var_os(DATA_DIR_ENV).is_some_and(|dir| !dir.is_empty()) {
+            return Err(DbError::InvalidInput(format!(
+                "the data directory is fixed by {DATA_DIR_ENV}"
+            )));
+        }
+        let current = self.file_path()?;
+        let default_dir = Self::default_storage_dir(handle)?;
+        let target_dir = match dir.map(str::trim).filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_dir.clone(),
+        };
+        if !target_dir.is_absolute() {
+            return Err(DbError::InvalidInput(format!(
+                "data directory must be an absolute path: {}",
+                target_dir.display()
+            )));
+        }
+        let file = profile_file(&Self::active_profile(handle)?);
+        let target = target_dir.join(&file);
+        if target == current {
+            return Ok(target);
+        }
+
+        create_storage_dir(&target_dir)?;
+        let probe = target_dir.join(".timemaster-write-test");
+        std::fs::write(&probe, b"").map_err(|err| read_only_storage(&target_dir, &err))?;
+        std::fs::remove_file(&probe)?;
+        if target.exists() {
+            let backup = format!("{file}.{}.bak", Utc::now().format("%Y%m%d%H%M%S"));
+            std::fs::rename(&target, target_dir.join(backup))?;
+        }
+
+        let conn = self.conn()?;
+        conn.execute("VACUUM INTO ?1", [target.to_string_lossy()])?;
+
+        create_storage_dir(&default_dir)?;
+        let pointer = default_dir.join(DATA_DIR_POINTER);
+        if target_dir == default_dir {
+            match std::fs::remove_file(&pointer) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        } else {
+            std::fs::write(&pointer, target_dir.to_string_lossy().as_bytes())?;
+        }
+        Ok(target)
     }
 
     fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>> {
-        Ok(self.pool.get()?)
+        let pool = self.pool();
+        if let Some(conn) = pool.try_get() {
+            return Ok(conn);
+        }
+        let mut last_error = None;
+        for attempt in 1..=POOL_RETRY_ATTEMPTS {
+            match pool.get_timeout(POOL_RETRY_BACKOFF * attempt) {
+                Ok(conn) => return Ok(conn),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        let Some(err) = last_error else {
+            return Err(DbError::PoolExhausted(String::new()));
+        };
+        // Only a pool whose every connection is checked out is "busy"; anything
+        // else means new connections could not be opened at all.
+        let state = pool.state();
+        if state.connections == pool.max_size() && state.idle_connections == 0 {
+            Err(DbError::PoolExhausted(err.to_string()))
+        } else {
+            Err(DbError::Pool(err))
+        }
     }
 
     fn run_migrations(&self) -> Result<()> {
@@ -77,47 +323,511 @@ impl Db {
                 updated_at TEXT NOT NULL
             );
             CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
+            CREATE TABLE IF NOT EXISTS time_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                started_at TEXT NOT NULL,
+                ended_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_time_entries_task ON time_entries(task_id);
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_time_entries_running
+                ON time_entries(task_id) WHERE ended_at IS NULL;
+            CREATE TABLE IF NOT EXISTS task_tags (
+                task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (task_id, tag)
+            );
+            CREATE INDEX IF NOT EXISTS idx_task_tags_tag ON task_tags(tag);
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS deleted_tasks (
+                id TEXT PRIMARY KEY,
+                deleted_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_deleted_tasks_at ON deleted_tasks(deleted_at);
             "#,
         )?;
+        add_column_if_missing(&conn, "tasks", "color", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "streak", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "tasks", "last_completed_at", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "snoozed_until", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "frozen_until", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "notes", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "parent_id", "TEXT")?;
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_tasks_parent ON tasks(parent_id);")?;
+        add_column_if_missing(&conn, "tasks", "reminder_time", "TEXT")?;
+        add_column_if_missing(&conn, "tasks", "priority", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "tasks", "regenerate_rule", "TEXT")?;
+        let previous: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if previous < SCHEMA_VERSION {
+            log::info!("migrating database schema from version {previous} to {SCHEMA_VERSION}");
+        }
+        if previous < 2 {
+            backfill_streaks(&conn)?;
+        }
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
         Ok(())
     }
 
-    pub fn list_tasks(&self, status: Option<String>) -> Result<Vec<Task>> {
+    pub fn backfill_streaks(&self) -> Result<usize> {
         let conn = self.conn()?;
-        if let Some(status) = status {
-            let mut stmt = conn.prepare(
-                "SELECT id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at FROM tasks WHERE status = ? ORDER BY updated_at DESC",
-            )?;
+        backfill_streaks(&conn)
+    }
+
+    pub fn repair_consistency(&self) -> Result<usize> {
+        let conn = self.conn()?;
+        let repaired = conn.execute(
+            "UPDATE tasks SET progress = MIN(progress, target),
+                status = CASE WHEN progress >= target THEN 'completed' ELSE 'active' END
+            WHERE status != 'archived'
+                AND (progress > target
+                    OR (progress >= target AND status != 'completed')
+                    OR (progress < target AND status = 'completed'))",
+            [],
+        )?;
+        Ok(repaired)
+    }
+
+    pub fn reconcile_imported(&self) -> Result<Vec<TaskAnomaly>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let tasks = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at ASC"
+            ))?;
             let rows = stmt
-                .query_map([status], Task::from_row)?
+                .query_map([], Task::from_row)?
                 .collect::<rusqlite::Result<Vec<_>>>()?;
-            Ok(rows)
-        } else {
-            let mut stmt = conn.prepare(
-                "SELECT id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at FROM tasks ORDER BY updated_at DESC",
+            rows
+        };
+
+        let now = Utc::now().to_rfc3339();
+        let mut anomalies = Vec::new();
+        for task in tasks {
+            let mut issues = Vec::new();
+            let target = task.target.clamp(1, MAX_TASK_TARGET);
+            if target != task.target {
+                issues.push("target_out_of_range".to_string());
+            }
+            if task.progress < 0 {
+                issues.push("negative_progress".to_string());
+            } else if task.progress > target {
+                issues.push("progress_exceeds_target".to_string());
+            }
+            let progress = task.progress.clamp(0, target);
+            let status = match task.status.as_str() {
+                "archived" => "archived",
+                _ if progress >= target => "completed",
+                _ => "active",
+            };
+            if status != task.status {
+                issues.push("status_mismatch".to_string());
+            }
+            if issues.is_empty() {
+                continue;
+            }
+
+            tx.execute(
+                "UPDATE tasks SET target = ?1, progress = ?2, status = ?3, updated_at = ?4 WHERE id = ?5",
+                params![target, progress, status, now, task.id],
             )?;
-            let rows = stmt
-                .query_map([], Task::from_row)?
+            anomalies.push(TaskAnomaly {
+                id: task.id,
+                name: task.name,
+                issues,
+            });
+        }
+        tx.commit()?;
+        Ok(anomalies)
+    }
+
+    pub fn list_tasks(
+        &self,
+        statuses: Option<Vec<String>>,
+        include_snoozed: bool,
+    ) -> Result<Vec<Task>> {
+        let statuses = statuses.unwrap_or_default();
+        for status in &statuses {
+            validate_status(status)?;
+        }
+
+        let conn = self.conn()?;
+        clear_expired_snoozes(&conn)?;
+        clear_expired_freezes(&conn)?;
+        let mut clauses = Vec::new();
+        if !statuses.is_empty() {
+            let placeholders = vec!["?"; statuses.len()].join(", ");
+            clauses.push(format!("status IN ({placeholders})"));
+        }
+        if !include_snoozed {
+            clauses.push("snoozed_until IS NULL".to_string());
+        }
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks{filter} ORDER BY updated_at DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(statuses.iter()), Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    pub fn search_tasks(&self, payload: SearchTasks) -> Result<Vec<SearchHit>> {
+        let query = payload.query.trim();
+        if query.is_empty() {
+            return Err(DbError::InvalidInput(
+                "search query must not be empty".into(),
+            ));
+        }
+
+        let conn = self.conn()?;
+        if !payload.fuzzy.unwrap_or(false) {
+            let pattern = like_pattern(query);
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks WHERE name LIKE ?1 ESCAPE '\\' OR description LIKE ?1 ESCAPE '\\' ORDER BY updated_at DESC"
+            ))?;
+            let hits = stmt
+                .query_map([pattern], Task::from_row)?
+                .map(|task| task.map(|task| SearchHit { task, score: 1.0 }))
                 .collect::<rusqlite::Result<Vec<_>>>()?;
-            Ok(rows)
+            return Ok(hits);
         }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks ORDER BY updated_at DESC"
+        ))?;
+        let mut hits = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter_map(|task| {
+                let score = fuzzy_score(query, &task.name);
+                (score >= FUZZY_MATCH_THRESHOLD).then_some(SearchHit { task, score })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(hits)
     }
 
-    pub fn create_task(&self, payload: NewTask) -> Result<Task> {
+    pub fn delta_tasks(
+        &self,
+        known: HashMap<String, String>,
+        include_snoozed: bool,
+    ) -> Result<TaskDelta> {
+        for (id, updated_at) in &known {
+            if id.trim().is_empty() {
+                return Err(DbError::InvalidInput("task id must not be empty".into()));
+            }
+            DateTime::parse_from_rfc3339(updated_at).map_err(|_| {
+                DbError::InvalidInput(format!("invalid updatedAt for task {id}: {updated_at}"))
+            })?;
+        }
+
+        let mut remaining = known;
+        let mut changed = Vec::new();
+        for task in self.list_tasks(None, include_snoozed)? {
+            if remaining.remove(&task.id).as_ref() != Some(&task.updated_at) {
+                changed.push(task);
+            }
+        }
+        // A known task missing from the listing may only be snoozed, so only
+        // ids that no longer exist at all count as deleted.
+        let conn = self.conn()?;
+        let mut exists = conn.prepare("SELECT 1 FROM tasks WHERE id = ?1")?;
+        let mut deleted = Vec::new();
+        for id in remaining.into_keys() {
+            if !exists.exists([&id])? {
+                deleted.push(id);
+            }
+        }
+        deleted.sort();
+        Ok(TaskDelta { changed, deleted })
+    }
+
+    pub fn list_grouped(&self) -> Result<GroupedTasks> {
+        let conn = self.conn()?;
+        clear_expired_snoozes(&conn)?;
+        clear_expired_freezes(&conn)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE snoozed_until IS NULL ORDER BY updated_at DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut grouped = GroupedTasks::default();
+        for task in rows {
+            match task.status.as_str() {
+                "completed" => grouped.completed.push(task),
+                "archived" => grouped.archived.push(task),
+                _ => grouped.active.push(task),
+            }
+        }
+        Ok(grouped)
+    }
+
+    pub fn week_agenda(&self) -> Result<Vec<AgendaDay>> {
+        let today = Local::now().date_naive();
+        let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE (task_type = 'long_term' AND status = 'active') OR (task_type = 'cycle' AND status != 'archived' AND (repeat_rule IS NULL OR repeat_rule = 'daily')) ORDER BY created_at ASC"
+        ))?;
+        let rows = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok((0..7)
+            .map(|offset| {
+                let day = week_start + Duration::days(offset);
+                AgendaDay {
+                    date: day.format("%Y-%m-%d").to_string(),
+                    weekday: day.format("%A").to_string().to_lowercase(),
+                    tasks: rows
+                        .iter()
+                        .filter(|task| task.task_type == "cycle" || date_range_contains(task, day))
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect())
+    }
+
+    pub fn list_tasks_in_range(&self, from: &str, to: &str, field: &str) -> Result<Vec<Task>> {
+        let column = match field {
+            "created_at" | "updated_at" => field,
+            other => {
+                return Err(DbError::InvalidInput(format!(
+                    "unknown timestamp field: {other}"
+                )))
+            }
+        };
+        let from = parse_bound(from, false)?;
+        let to = parse_bound(to, true)?;
+        if from > to {
+            return Err(DbError::InvalidInput(
+                "range start must not be after range end".into(),
+            ));
+        }
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE {column} BETWEEN ?1 AND ?2 ORDER BY {column} DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params![from.to_rfc3339(), to.to_rfc3339()], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    pub fn completed_on(&self, date: &str) -> Result<Vec<Task>> {
+        let day = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|_| DbError::InvalidInput(format!("invalid date: {date}")))?;
+        let next = day
+            .succ_opt()
+            .ok_or_else(|| DbError::InvalidInput(format!("invalid date: {date}")))?;
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE status = 'completed' AND updated_at >= ?1 AND updated_at < ?2 ORDER BY updated_at ASC"
+        ))?;
+        let rows = stmt
+            .query_map(
+                params![
+                    local_day_start(day).to_rfc3339(),
+                    local_day_start(next).to_rfc3339()
+                ],
+                Task::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Lists tasks updated after `since` along with the ids of tasks deleted
+    /// after it, so a client can bring its cache up to date in one call.
+    pub fn list_changed_since(&self, since: &str) -> Result<TaskDelta> {
+        let since = DateTime::parse_from_rfc3339(since.trim())
+            .map_err(|_| DbError::InvalidInput(format!("invalid timestamp: {since}")))?
+            .with_timezone(&Utc)
+            .to_rfc3339();
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE updated_at > ?1 ORDER BY updated_at ASC"
+        ))?;
+        let changed = stmt
+            .query_map([&since], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        // An id that has been imported again since its deletion is not gone.
+        let mut stmt = conn.prepare(
+            "SELECT id FROM deleted_tasks
+            WHERE deleted_at > ?1 AND id NOT IN (SELECT id FROM tasks)
+            ORDER BY deleted_at ASC",
+        )?;
+        let deleted = stmt
+            .query_map([&since], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(TaskDelta { changed, deleted })
+    }
+
+    pub fn query_tasks(&self, query: TaskQuery) -> Result<Vec<Task>> {
+        let mut clauses: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        let statuses = query.statuses.unwrap_or_default();
+        if !statuses.is_empty() {
+            for status in &statuses {
+                validate_status(status)?;
+            }
+            clauses.push(format!(
+                "status IN ({})",
+                vec!["?"; statuses.len()].join(", ")
+            ));
+            values.extend(statuses.into_iter().map(Value::Text));
+        }
+        if let Some(task_type) = query.task_type {
+            validate_task_type(&task_type)?;
+            clauses.push("task_type = ?".into());
+            values.push(Value::Text(task_type));
+        }
+        if let Some(tag) = query.tag {
+            clauses.push("id IN (SELECT task_id FROM task_tags WHERE tag = ?)".into());
+            values.push(Value::Text(normalize_tag(&tag)?));
+        }
+        push_time_bounds(
+            &mut clauses,
+            &mut values,
+            "created_at",
+            query.created_from,
+            query.created_to,
+        )?;
+        push_time_bounds(
+            &mut clauses,
+            &mut values,
+            "updated_at",
+            query.updated_from,
+            query.updated_to,
+        )?;
+
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
         let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks{filter} ORDER BY updated_at DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(values), Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    pub fn get_today(&self, include_completed: bool, include_snoozed: bool) -> Result<Vec<Task>> {
+        let today = Local::now().date_naive();
+        let conn = self.conn()?;
+        clear_expired_snoozes(&conn)?;
+        clear_expired_freezes(&conn)?;
+        let snooze_filter = if include_snoozed {
+            ""
+        } else {
+            " AND snoozed_until IS NULL"
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE ((task_type = 'cycle' AND status IN ('active', 'completed')) OR (task_type = 'long_term' AND status = 'active')){snooze_filter} ORDER BY updated_at DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows
+            .into_iter()
+            .filter(|task| match task.task_type.as_str() {
+                "cycle" if task.status == "completed" => {
+                    include_completed && local_date(&task.updated_at) == Some(today)
+                }
+                "cycle" => true,
+                _ => date_range_contains(task, today),
+            })
+            .collect())
+    }
+
+    /// Picks the highest-priority actionable task, where a larger priority
+    /// number ranks first, then the nearest end date, then the oldest.
+    pub fn next_task(&self) -> Result<Option<Task>> {
+        Ok(self.get_today(false, false)?.into_iter().min_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| a.end_date.is_none().cmp(&b.end_date.is_none()))
+                .then_with(|| a.end_date.cmp(&b.end_date))
+                .then_with(|| a.created_at.cmp(&b.created_at))
+        }))
+    }
+
+    pub fn create_task(&self, payload: NewTask) -> Result<Task> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let task = self.insert_task(&tx, payload)?;
+        tx.commit()?;
+        Ok(task)
+    }
+
+    pub fn create_tasks(&self, payloads: Vec<NewTask>) -> Result<Vec<Task>> {
+        if payloads.len() > MAX_BATCH_SIZE {
+            return Err(DbError::InvalidInput(format!(
+                "at most {MAX_BATCH_SIZE} tasks can be created at once"
+            )));
+        }
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let tasks = payloads
+            .into_iter()
+            .enumerate()
+            .map(|(index, payload)| {
+                self.insert_task(&tx, payload).map_err(|err| match err {
+                    DbError::InvalidInput(message) => {
+                        DbError::InvalidInput(format!("task {}: {message}", index + 1))
+                    }
+                    other => other,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(tasks)
+    }
+
+    fn insert_task(&self, tx: &rusqlite::Connection, payload: NewTask) -> Result<Task> {
+        payload.validate()?;
+        enforce_daily_cap(tx, &payload.task_type)?;
         let now = Utc::now().to_rfc3339();
         let id = payload.id.unwrap_or_else(|| Uuid::new_v4().to_string());
-        let target = payload.target.unwrap_or(1).max(1);
+        let target = payload.target.unwrap_or(1);
         let (repeat_rule, start_date, end_date) =
             normalize_schedule(payload.task_type.as_str(), payload.repeat.clone(), payload.date_range.clone())?;
+        let color = normalize_color(payload.color)?;
+        let notes = normalize_notes(payload.notes);
+        let regenerate_rule = normalize_regenerate(payload.task_type.as_str(), payload.regenerate)?;
+        let reminder_time = normalize_reminder_time(payload.reminder_time)?
+            .filter(|_| payload.task_type == "cycle");
+        let name = normalize_name(&payload.name)?;
+        let description = normalize_description(&payload.description.unwrap_or_default())?;
 
-        conn.execute(
-            "INSERT INTO tasks (id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        tx.execute(
+            "INSERT INTO tasks (id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at, color, notes, regenerate_rule, reminder_time)
+            VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             params![
                 id,
-                payload.name.trim(),
-                payload.description.unwrap_or_default().trim(),
+                name,
+                description,
                 payload.task_type,
                 target,
                 repeat_rule,
@@ -125,30 +835,115 @@ impl Db {
                 end_date,
                 "active",
                 now,
-                now
+                now,
+                color,
+                notes,
+                regenerate_rule,
+                reminder_time
             ],
         )?;
 
-        self.fetch_task(&conn, &id)
+        self.fetch_task(tx, &id)
     }
 
-    pub fn update_task(&self, payload: UpdateTask) -> Result<Task> {
+    pub fn run_query(&self, sql: &str) -> Result<QueryResult> {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        if sql.contains(';') {
+            return Err(DbError::InvalidInput(
+                "only a single statement may be run".into(),
+            ));
+        }
+        let keyword = sql
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        if keyword != "SELECT" && keyword != "WITH" {
+            return Err(DbError::InvalidInput(
+                "only SELECT queries may be run".into(),
+            ));
+        }
+
+        // Pooled connections are reused, so query_only has to be switched
+        // back off whether or not the query succeeds.
+        let conn = self.conn()?;
+        conn.pragma_update(None, "query_only", true)?;
+        let result = read_query(&conn, sql);
+        conn.pragma_update(None, "query_only", false)?;
+        result
+    }
+
+    pub fn daily_caps(&self) -> Result<Vec<DailyCap>> {
         let conn = self.conn()?;
-        let mut existing = self.fetch_task(&conn, &payload.id)?;
+        TASK_TYPES
+            .iter()
+            .map(|task_type| {
+                Ok(DailyCap {
+                    task_type: task_type.to_string(),
+                    cap: daily_cap(&conn, task_type)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_daily_cap(&self, payload: DailyCap) -> Result<DailyCap> {
+        validate_task_type(&payload.task_type)?;
+        let key = format!("{SETTING_DAILY_CAP_PREFIX}{}", payload.task_type);
+        match payload.cap {
+            Some(cap) if cap < 0 => {
+                return Err(DbError::InvalidInput(
+                    "daily cap must not be negative".into(),
+                ))
+            }
+            Some(cap) => self.set_setting(&key, &cap.to_string())?,
+            None => {
+                let conn = self.conn()?;
+                conn.execute("DELETE FROM settings WHERE key = ?1", [&key])?;
+            }
+        }
+        Ok(payload)
+    }
+
+    pub fn update_task(&self, payload: UpdateTask) -> Result<Task> {
+        if let Some(target) = payload.target {
+            validate_target(target)?;
+        }
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut existing = self.fetch_task(&tx, &payload.id)?;
 
         let (repeat_rule, start_date, end_date) =
             normalize_schedule(payload.task_type.as_str(), payload.repeat.clone(), payload.date_range.clone())?;
-        existing.name = payload.name.trim().to_string();
-        existing.description = payload
-            .description
-            .unwrap_or_default()
-            .trim()
-            .to_string();
+        existing.name = normalize_name(&payload.name)?;
+        existing.description = normalize_description(&payload.description.unwrap_or_default())?;
         existing.task_type = payload.task_type;
-        existing.target = payload.target.unwrap_or(existing.target).max(1);
+        existing.target = payload.target.unwrap_or(existing.target);
         existing.repeat_rule = repeat_rule;
         existing.start_date = start_date;
         existing.end_date = end_date;
+        // An omitted color keeps the current one; an empty string clears it.
+        if let Some(color) = payload.color {
+            existing.color = normalize_color(Some(color))?;
+        }
+        // The same goes for notes.
+        if let Some(notes) = payload.notes {
+            existing.notes = normalize_notes(Some(notes));
+        }
+        // Like color, an omitted regenerate rule or reminder keeps the current one.
+        if let Some(rule) = payload.regenerate {
+            existing.regenerate_rule =
+                normalize_regenerate(existing.task_type.as_str(), Some(rule))?;
+        }
+        if let Some(time) = payload.reminder_time {
+            existing.reminder_time = normalize_reminder_time(Some(time))?;
+        }
+        if existing.task_type != "long_term" {
+            existing.regenerate_rule = None;
+        }
+        if existing.task_type != "cycle" {
+            existing.frozen_until = None;
+            existing.reminder_time = None;
+        }
         existing.updated_at = Utc::now().to_rfc3339();
 
         if existing.progress > existing.target {
@@ -163,8 +958,8 @@ impl Db {
             };
         }
 
-        conn.execute(
-            "UPDATE tasks SET name = ?1, description = ?2, task_type = ?3, target = ?4, repeat_rule = ?5, start_date = ?6, end_date = ?7, progress = ?8, status = ?9, updated_at = ?10 WHERE id = ?11",
+        tx.execute(
+            "UPDATE tasks SET name = ?1, description = ?2, task_type = ?3, target = ?4, repeat_rule = ?5, start_date = ?6, end_date = ?7, progress = ?8, status = ?9, updated_at = ?10, color = ?11, notes = ?12, regenerate_rule = ?13, frozen_until = ?14, reminder_time = ?15 WHERE id = ?16",
             params![
                 existing.name,
                 existing.description,
@@ -176,87 +971,2142 @@ impl Db {
                 existing.progress,
                 existing.status,
                 existing.updated_at,
+                existing.color,
+                existing.notes,
+                existing.regenerate_rule,
+                existing.frozen_until,
+                existing.reminder_time,
                 existing.id
             ],
         )?;
 
-        self.fetch_task(&conn, &existing.id)
+        let task = self.fetch_task(&tx, &existing.id)?;
+        tx.commit()?;
+        Ok(task)
     }
 
-    pub fn delete_task(&self, id: &str) -> Result<()> {
-        let conn = self.conn()?;
-        conn.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
-        Ok(())
-    }
+    pub fn patch_task(&self, payload: PatchTask) -> Result<ProgressUpdate> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let existing = self.fetch_task(&tx, &payload.id)?;
+        let mut changes: Vec<(&str, Value)> = Vec::new();
 
-    pub fn increment_progress(&self, id: &str) -> Result<Task> {
-        let conn = self.conn()?;
-        let mut task = self.fetch_task(&conn, id)?;
+        if let Some(name) = payload.name {
+            changes.push(("name", Value::Text(normalize_name(&name)?)));
+        }
+        if let Some(description) = payload.description {
+            changes.push((
+                "description",
+                Value::Text(normalize_description(&description)?),
+            ));
+        }
+        if payload.color.is_some() {
+            let color = normalize_color(payload.color)?;
+            changes.push(("color", color.map_or(Value::Null, Value::Text)));
+        }
+        if payload.notes.is_some() {
+            let notes = normalize_notes(payload.notes);
+            changes.push(("notes", notes.map_or(Value::Null, Value::Text)));
+        }
 
-        if task.status == "archived" {
-            return Err(DbError::InvalidInput("cannot update archived task".into()));
+        if payload.task_type.is_some() || payload.repeat.is_some() || payload.date_range.is_some() {
+            let task_type = payload.task_type.unwrap_or(existing.task_type.clone());
+            validate_task_type(&task_type)?;
+            let repeat = payload.repeat.or(existing.repeat_rule.clone());
+            let date_range =
+                payload
+                    .date_range
+                    .or_else(|| match (&existing.start_date, &existing.end_date) {
+                        (Some(start), Some(end)) => Some(vec![start.clone(), end.clone()]),
+                        _ => None,
+                    });
+            let (repeat_rule, start_date, end_date) =
+                normalize_schedule(task_type.as_str(), repeat, date_range)?;
+            if task_type != "long_term" {
+                changes.push(("regenerate_rule", Value::Null));
+            }
+            if task_type != "cycle" {
+                changes.push(("frozen_until", Value::Null));
+                changes.push(("reminder_time", Value::Null));
+            }
+            changes.push(("task_type", Value::Text(task_type)));
+            changes.push(("repeat_rule", repeat_rule.map_or(Value::Null, Value::Text)));
+            changes.push(("start_date", start_date.map_or(Value::Null, Value::Text)));
+            changes.push(("end_date", end_date.map_or(Value::Null, Value::Text)));
         }
 
-        if task.progress < task.target {
-            task.progress += 1;
+        if let Some(target) = payload.target {
+            validate_target(target)?;
+            changes.push(("target", Value::Integer(target)));
         }
-        if task.progress >= task.target {
-            task.status = "completed".into();
+
+        if changes.is_empty() {
+            return Ok(ProgressUpdate {
+                task: existing,
+                just_completed: false,
+                regenerated: None,
+            });
         }
-        task.updated_at = Utc::now().to_rfc3339();
+        changes.push(("updated_at", Value::Text(Utc::now().to_rfc3339())));
 
-        conn.execute(
-            "UPDATE tasks SET progress = ?1, status = ?2, updated_at = ?3 WHERE id = ?4",
-            params![task.progress, task.status, task.updated_at, task.id],
+        let mut assignments = changes
+            .iter()
+            .enumerate()
+            .map(|(index, (column, _))| format!("{column} = ?{}", index + 1))
+            .collect::<Vec<_>>();
+        let mut values: Vec<Value> = changes.into_iter().map(|(_, value)| value).collect();
+        // A new target clamps the stored progress and settles the status the
+        // same way adjust_target does.
+        if let Some(target) = payload.target {
+            values.push(Value::Integer(target));
+            let slot = values.len();
+            assignments.push(format!("progress = MIN(progress, ?{slot})"));
+            assignments.push(format!(
+                "status = CASE WHEN status = 'archived' THEN status WHEN MIN(progress, ?{slot}) >= ?{slot} THEN 'completed' ELSE 'active' END"
+            ));
+        }
+        values.push(Value::Text(existing.id.clone()));
+        tx.execute(
+            &format!(
+                "UPDATE tasks SET {} WHERE id = ?{}",
+                assignments.join(", "),
+                values.len()
+            ),
+            params_from_iter(values),
         )?;
-
-        Ok(task)
-    }
-
-    pub fn archive_task(&self, id: &str) -> Result<Task> {
-        self.update_status(id, "archived")
+        let update = self.settle_progress(&tx, &existing)?;
+        tx.commit()?;
+        Ok(update)
     }
 
-    pub fn reopen_task(&self, id: &str) -> Result<Task> {
-        let task = self.update_status(id, "active")?;
-        if task.task_type == "cycle" {
-            let conn = self.conn()?;
-            conn.execute(
-                "UPDATE tasks SET progress = 0, updated_at = ?1 WHERE id = ?2",
-                params![Utc::now().to_rfc3339(), task.id],
-            )?;
-            return self.fetch_task(&conn, &task.id);
+    pub fn rename_task(&self, payload: RenameTask) -> Result<Task> {
+        let name = normalize_name(&payload.name)?;
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE tasks SET name = ?1, updated_at = ?2 WHERE id = ?3",
+            params![name, Utc::now().to_rfc3339(), payload.id],
+        )?;
+        if updated == 0 {
+            return Err(DbError::NotFound(format!("task {}", payload.id)));
         }
-        Ok(task)
+        self.fetch_task(&conn, &payload.id)
     }
 
-    fn update_status(&self, id: &str, status: &str) -> Result<Task> {
+    pub fn change_task_type(&self, payload: ChangeTaskType) -> Result<Task> {
+        validate_task_type(&payload.task_type)?;
         let conn = self.conn()?;
-        let updated_at = Utc::now().to_rfc3339();
+        let existing = self.fetch_task(&conn, &payload.id)?;
+
+        let (repeat_rule, start_date, end_date) = normalize_schedule(
+            payload.task_type.as_str(),
+            payload.repeat,
+            payload.date_range,
+        )?;
+
         conn.execute(
-            "UPDATE tasks SET status = ?1, updated_at = ?2 WHERE id = ?3",
-            params![status, updated_at, id],
+            "UPDATE tasks SET task_type = ?1, repeat_rule = ?2, start_date = ?3, end_date = ?4, updated_at = ?5,
+                regenerate_rule = CASE WHEN ?1 = 'long_term' THEN regenerate_rule END,
+                frozen_until = CASE WHEN ?1 = 'cycle' THEN frozen_until END,
+                reminder_time = CASE WHEN ?1 = 'cycle' THEN reminder_time END
+            WHERE id = ?6",
+            params![
+                payload.task_type,
+                repeat_rule,
+                start_date,
+                end_date,
+                Utc::now().to_rfc3339(),
+                existing.id
+            ],
         )?;
-        self.fetch_task(&conn, id)
-    }
 
-    fn fetch_task(&self, conn: &rusqlite::Connection, id: &str) -> Result<Task> {
-        conn
-            .query_row(
-                "SELECT id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at FROM tasks WHERE id = ?1",
-                [id],
-                Task::from_row,
-            )
-            .optional()
-            .map_err(DbError::from)?
-            .ok_or_else(|| DbError::InvalidInput(format!("task {id} not found")))
+        self.fetch_task(&conn, &existing.id)
     }
-}
 
-fn normalize_schedule(
-    task_type: &str,
-    repeat: Option<String>,
-    date_range: Option<Vec<String>>,
+    pub fn clear_schedule(&self, id: &str) -> Result<Task> {
+        let conn = self.conn()?;
+        let existing = self.fetch_task(&conn, id)?;
+        let status = if existing.status == "archived" {
+            "archived"
+        } else if existing.progress >= existing.target {
+            "completed"
+        } else {
+            "active"
+        };
+
+        conn.execute(
+            "UPDATE tasks SET task_type = 'once', repeat_rule = NULL, start_date = NULL, end_date = NULL, regenerate_rule = NULL, frozen_until = NULL, reminder_time = NULL, status = ?1, updated_at = ?2 WHERE id = ?3",
+            params![status, Utc::now().to_rfc3339(), existing.id],
+        )?;
+
+        self.fetch_task(&conn, &existing.id)
+    }
+
+    pub fn merge_tasks(&self, payload: MergeTasks) -> Result<Task> {
+        if payload.primary_id == payload.secondary_id {
+            return Err(DbError::InvalidInput(
+                "cannot merge a task with itself".into(),
+            ));
+        }
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let primary = self.fetch_task(&tx, &payload.primary_id)?;
+        let secondary = self.fetch_task(&tx, &payload.secondary_id)?;
+        if primary.task_type != secondary.task_type {
+            return Err(DbError::InvalidInput(format!(
+                "cannot merge a {} task into a {} task",
+                secondary.task_type, primary.task_type
+            )));
+        }
+
+        let progress = (primary.progress + secondary.progress).min(primary.target);
+        let status = if primary.status == "archived" {
+            "archived"
+        } else if progress >= primary.target {
+            "completed"
+        } else {
+            "active"
+        };
+        tx.execute(
+            "UPDATE tasks SET progress = ?1, status = ?2, updated_at = ?3 WHERE id = ?4",
+            params![progress, status, Utc::now().to_rfc3339(), primary.id],
+        )?;
+        tx.execute(
+            "UPDATE time_entries SET ended_at = ?1 WHERE task_id = ?2 AND ended_at IS NULL",
+            params![Utc::now().to_rfc3339(), secondary.id],
+        )?;
+        tx.execute(
+            "UPDATE time_entries SET task_id = ?1 WHERE task_id = ?2",
+            params![primary.id, secondary.id],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag) SELECT ?1, tag FROM task_tags WHERE task_id = ?2",
+            params![primary.id, secondary.id],
+        )?;
+        tx.execute("DELETE FROM task_tags WHERE task_id = ?1", [&secondary.id])?;
+        tx.execute("DELETE FROM tasks WHERE id = ?1", [&secondary.id])?;
+        record_deletion(&tx, &secondary.id)?;
+        let merged = self.fetch_task(&tx, &primary.id)?;
+        tx.commit()?;
+        Ok(merged)
+    }
+
+    pub fn get_task(&self, id: &str) -> Result<Task> {
+        let conn = self.conn()?;
+        self.fetch_task(&conn, id)
+    }
+
+    pub fn delete_task(&self, id: &str) -> Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute("DELETE FROM time_entries WHERE task_id = ?1", [id])?;
+        tx.execute("DELETE FROM task_tags WHERE task_id = ?1", [id])?;
+        if tx.execute("DELETE FROM tasks WHERE id = ?1", [id])? > 0 {
+            record_deletion(&tx, id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn set_task_tags(&self, payload: SetTaskTags) -> Result<Task> {
+        let tags = payload
+            .tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<BTreeSet<_>>>()?;
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        self.fetch_task(&tx, &payload.id)?;
+        tx.execute("DELETE FROM task_tags WHERE task_id = ?1", [&payload.id])?;
+        for tag in &tags {
+            tx.execute(
+                "INSERT INTO task_tags (task_id, tag) VALUES (?1, ?2)",
+                params![payload.id, tag],
+            )?;
+        }
+        tx.execute(
+            "UPDATE tasks SET updated_at = ?1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), payload.id],
+        )?;
+        let task = self.fetch_task(&tx, &payload.id)?;
+        tx.commit()?;
+        Ok(task)
+    }
+
+    /// Moves every task tagged `from` onto `to`, folding into `to` when some
+    /// tasks already carry it. Returns the number of tasks that were retagged.
+    pub fn rename_tag(&self, from: &str, to: &str) -> Result<usize> {
+        let from = normalize_tag(from)?;
+        let to = normalize_tag(to)?;
+        if from == to {
+            return Ok(0);
+        }
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute(
+            "UPDATE tasks SET updated_at = ?1 WHERE id IN (SELECT task_id FROM task_tags WHERE tag = ?2)",
+            params![Utc::now().to_rfc3339(), from],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag) SELECT task_id, ?1 FROM task_tags WHERE tag = ?2",
+            params![to, from],
+        )?;
+        let affected = tx.execute("DELETE FROM task_tags WHERE tag = ?1", [&from])?;
+        tx.commit()?;
+        Ok(affected)
+    }
+
+    pub fn set_parent(&self, payload: SetParent) -> Result<Task> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        self.fetch_task(&tx, &payload.id)?;
+        if let Some(parent_id) = &payload.parent_id {
+            validate_parent(&tx, &payload.id, parent_id)?;
+        }
+        tx.execute(
+            "UPDATE tasks SET parent_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![payload.parent_id, Utc::now().to_rfc3339(), payload.id],
+        )?;
+        let task = self.fetch_task(&tx, &payload.id)?;
+        tx.commit()?;
+        Ok(task)
+    }
+
+    /// Flattens the subtask hierarchy in pre-order, starting at `root` or at
+    /// every top-level task. A task whose parent no longer exists counts as
+    /// top-level, and members of a parent cycle are emitted once as roots.
+    pub fn list_tree(&self, root: Option<&str>) -> Result<Vec<TreeNode>> {
+        let conn = self.conn()?;
+        let roots = match root {
+            Some(id) => vec![self.fetch_task(&conn, id)?],
+            None => Vec::new(),
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at ASC, id ASC"
+        ))?;
+        let tasks = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let ids: BTreeSet<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+        let mut children: HashMap<&str, Vec<&Task>> = HashMap::new();
+        let mut top_level = Vec::new();
+        for task in &tasks {
+            match task
+                .parent_id
+                .as_deref()
+                .filter(|parent| ids.contains(parent))
+            {
+                Some(parent) => children.entry(parent).or_default().push(task),
+                None => top_level.push(task),
+            }
+        }
+        let starts: Vec<&Task> = if root.is_some() {
+            roots.iter().collect()
+        } else {
+            top_level.into_iter().chain(tasks.iter()).collect()
+        };
+
+        let mut seen = BTreeSet::new();
+        let mut nodes = Vec::new();
+        for start in starts {
+            let mut pending = vec![(start, 0)];
+            while let Some((task, depth)) = pending.pop() {
+                if !seen.insert(task.id.as_str()) {
+                    continue;
+                }
+                nodes.push(TreeNode {
+                    task: task.clone(),
+                    depth,
+                });
+                if let Some(kids) = children.get(task.id.as_str()) {
+                    pending.extend(kids.iter().rev().map(|kid| (*kid, depth + 1)));
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Tasks whose parent was deleted or archived.
+    pub fn find_orphans(&self) -> Result<Vec<Task>> {
+        let conn = self.conn()?;
+        find_orphans(&conn)
+    }
+
+    /// Moves every orphan under `reparent_to`, or detaches them to the top
+    /// level when it is `None`. Returns the orphans as they were found.
+    pub fn resolve_orphans(&self, payload: ResolveOrphans) -> Result<Vec<Task>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let orphans = find_orphans(&tx)?;
+        let now = Utc::now().to_rfc3339();
+        for orphan in &orphans {
+            if let Some(parent_id) = &payload.reparent_to {
+                validate_parent(&tx, &orphan.id, parent_id)?;
+            }
+            tx.execute(
+                "UPDATE tasks SET parent_id = ?1, updated_at = ?2 WHERE id = ?3",
+                params![payload.reparent_to, now, orphan.id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(orphans)
+    }
+
+    pub fn set_priority_bulk(&self, changes: Vec<PriorityChange>) -> Result<usize> {
+        for change in &changes {
+            validate_priority(change.priority)?;
+        }
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let now = Utc::now().to_rfc3339();
+        for change in &changes {
+            let updated = tx.execute(
+                "UPDATE tasks SET priority = ?1, updated_at = ?2 WHERE id = ?3",
+                params![change.priority, now, change.id],
+            )?;
+            if updated == 0 {
+                return Err(DbError::NotFound(format!("task {}", change.id)));
+            }
+        }
+        tx.commit()?;
+        Ok(changes.len())
+    }
+
+    pub fn start_timer(&self, id: &str) -> Result<TimeEntry> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        self.fetch_task(&tx, id)?;
+        let running: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM time_entries WHERE task_id = ?1 AND ended_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if running.is_some() {
+            return Err(DbError::InvalidInput(format!(
+                "a timer is already running for task {id}"
+            )));
+        }
+
+        let started_at = Utc::now().to_rfc3339();
+        tx.execute(
+            "INSERT INTO time_entries (task_id, started_at) VALUES (?1, ?2)",
+            params![id, started_at],
+        )?;
+        let entry = TimeEntry {
+            id: tx.last_insert_rowid(),
+            task_id: id.to_string(),
+            started_at,
+            ended_at: None,
+            duration_seconds: 0,
+        };
+        tx.commit()?;
+        Ok(entry)
+    }
+
+    pub fn stop_timer(&self, id: &str) -> Result<Option<TimeEntry>> {
+        let conn = self.conn()?;
+        let running: Option<(i64, String)> = conn
+            .query_row(
+                "SELECT id, started_at FROM time_entries WHERE task_id = ?1 AND ended_at IS NULL",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((entry_id, started_at)) = running else {
+            return Ok(None);
+        };
+
+        let ended_at = Utc::now().to_rfc3339();
+        conn.execute(
+            "UPDATE time_entries SET ended_at = ?1 WHERE id = ?2",
+            params![ended_at, entry_id],
+        )?;
+        Ok(Some(TimeEntry {
+            id: entry_id,
+            task_id: id.to_string(),
+            duration_seconds: entry_seconds(&started_at, Some(&ended_at)),
+            started_at,
+            ended_at: Some(ended_at),
+        }))
+    }
+
+    pub fn total_time_spent(&self, id: &str) -> Result<TimeSpent> {
+        let conn = self.conn()?;
+        self.fetch_task(&conn, id)?;
+        let mut stmt =
+            conn.prepare("SELECT started_at, ended_at FROM time_entries WHERE task_id = ?1")?;
+        let entries = stmt
+            .query_map([id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(TimeSpent {
+            id: id.to_string(),
+            running: entries.iter().any(|(_, ended_at)| ended_at.is_none()),
+            total_seconds: entries
+                .iter()
+                .map(|(started_at, ended_at)| entry_seconds(started_at, ended_at.as_deref()))
+                .sum(),
+        })
+    }
+
+    pub fn increment_progress(&self, id: &str) -> Result<ProgressUpdate> {
+        self.apply_progress(
+            id,
+            "UPDATE tasks SET progress = MIN(progress + 1, target),
+                status = CASE WHEN progress + 1 >= target THEN 'completed' ELSE status END,
+                updated_at = ?1
+            WHERE id = ?2 AND status != 'archived'",
+        )
+    }
+
+    pub fn adjust_target(&self, payload: AdjustTarget) -> Result<ProgressUpdate> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let previous = self.fetch_task(&tx, &payload.id)?;
+        if previous.status == "archived" {
+            return Err(DbError::InvalidInput("cannot update archived task".into()));
+        }
+
+        let target = previous
+            .target
+            .saturating_add(payload.delta)
+            .max(previous.progress)
+            .max(1);
+        validate_target(target)?;
+        let status = if previous.progress >= target {
+            "completed"
+        } else {
+            "active"
+        };
+        tx.execute(
+            "UPDATE tasks SET target = ?1, status = ?2, updated_at = ?3 WHERE id = ?4",
+            params![target, status, Utc::now().to_rfc3339(), previous.id],
+        )?;
+        let update = self.settle_progress(&tx, &previous)?;
+        tx.commit()?;
+        Ok(update)
+    }
+
+    pub fn complete_task(&self, id: &str) -> Result<ProgressUpdate> {
+        self.apply_progress(
+            id,
+            "UPDATE tasks SET progress = target, status = 'completed', updated_at = ?1
+            WHERE id = ?2 AND status != 'archived'",
+        )
+    }
+
+    pub fn toggle_complete(&self, id: &str) -> Result<ProgressUpdate> {
+        let task = self.get_task(id)?;
+        match task.status.as_str() {
+            "archived" => Err(DbError::InvalidInput("cannot update archived task".into())),
+            "completed" => Ok(ProgressUpdate {
+                task: self.uncomplete_task(id)?,
+                just_completed: false,
+                regenerated: None,
+            }),
+            _ => self.complete_task(id),
+        }
+    }
+
+    fn uncomplete_task(&self, id: &str) -> Result<Task> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let task = self.fetch_task(&tx, id)?;
+        if task.status != "completed" {
+            return Err(DbError::InvalidInput(format!("task {id} is not completed")));
+        }
+
+        let now = Utc::now();
+        let progress = if task.task_type == "cycle" {
+            0
+        } else {
+            task.target - 1
+        };
+        tx.execute(
+            "UPDATE tasks SET progress = ?1, status = 'active', updated_at = ?2 WHERE id = ?3",
+            params![progress, now.to_rfc3339(), id],
+        )?;
+        if task.task_type == "cycle" {
+            rollback_cycle_completion(&tx, &task, now)?;
+        }
+        let task = self.fetch_task(&tx, id)?;
+        tx.commit()?;
+        Ok(task)
+    }
+
+    fn apply_progress(&self, id: &str, sql: &str) -> Result<ProgressUpdate> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let previous = self.fetch_task(&tx, id)?;
+        if previous.status == "archived" {
+            return Err(DbError::InvalidInput("cannot update archived task".into()));
+        }
+
+        tx.execute(sql, params![Utc::now().to_rfc3339(), id])?;
+        let update = self.settle_progress(&tx, &previous)?;
+        tx.commit()?;
+        Ok(update)
+    }
+
+    /// Re-reads a task after a progress or target write and, when that write
+    /// completed it, records the cycle streak and spawns the next occurrence.
+    fn settle_progress(
+        &self,
+        tx: &rusqlite::Connection,
+        previous: &Task,
+    ) -> Result<ProgressUpdate> {
+        let task = self.fetch_task(tx, &previous.id)?;
+        let just_completed = previous.status != "completed" && task.status == "completed";
+        let regenerated = if just_completed {
+            record_cycle_completion(tx, &task)?;
+            regenerate_task(tx, &task)?
+                .map(|id| self.fetch_task(tx, &id))
+                .transpose()?
+        } else {
+            None
+        };
+        let task = if regenerated.is_some() {
+            self.fetch_task(tx, &previous.id)?
+        } else {
+            task
+        };
+        Ok(ProgressUpdate {
+            just_completed,
+            task,
+            regenerated,
+        })
+    }
+
+    pub fn snooze_task(&self, payload: SnoozeTask) -> Result<Task> {
+        let until = match payload.until.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(value) => {
+                let until = DateTime::parse_from_rfc3339(value)
+                    .map_err(|_| DbError::InvalidInput(format!("invalid timestamp: {value}")))?
+                    .with_timezone(&Utc);
+                if until <= Utc::now() {
+                    return Err(DbError::InvalidInput(
+                        "snooze time must be in the future".into(),
+                    ));
+                }
+                Some(until.to_rfc3339())
+            }
+        };
+
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE tasks SET snoozed_until = ?1, updated_at = ?2 WHERE id = ?3",
+            params![until, Utc::now().to_rfc3339(), payload.id],
+        )?;
+        if updated == 0 {
+            return Err(DbError::NotFound(format!("task {}", payload.id)));
+        }
+        self.fetch_task(&conn, &payload.id)
+    }
+
+    pub fn set_reminder_time(&self, payload: SetReminderTime) -> Result<Task> {
+        let time = normalize_reminder_time(payload.time)?;
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE tasks SET reminder_time = ?1, updated_at = ?2 WHERE id = ?3",
+            params![time, Utc::now().to_rfc3339(), payload.id],
+        )?;
+        if updated == 0 {
+            return Err(DbError::NotFound(format!("task {}", payload.id)));
+        }
+        self.fetch_task(&conn, &payload.id)
+    }
+
+    pub fn due_reminders(&self, time: &str) -> Result<Vec<Task>> {
+        let conn = self.conn()?;
+        clear_expired_snoozes(&conn)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE task_type = 'cycle' AND status = 'active' AND snoozed_until IS NULL AND reminder_time = ?1 ORDER BY created_at ASC"
+        ))?;
+        let rows = stmt
+            .query_map([time], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    pub fn freeze_task(&self, payload: FreezeTask) -> Result<Task> {
+        let until = match payload.until.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(value) => {
+                let until = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map_err(|_| DbError::InvalidInput(format!("invalid date: {value}")))?;
+                if until < Local::now().date_naive() {
+                    return Err(DbError::InvalidInput(
+                        "freeze date must not be in the past".into(),
+                    ));
+                }
+                Some(until.format("%Y-%m-%d").to_string())
+            }
+        };
+
+        let conn = self.conn()?;
+        let task = self.fetch_task(&conn, &payload.id)?;
+        if task.task_type != "cycle" {
+            return Err(DbError::InvalidInput(format!(
+                "task {} is not a cycle task",
+                task.id
+            )));
+        }
+        conn.execute(
+            "UPDATE tasks SET frozen_until = ?1, updated_at = ?2 WHERE id = ?3",
+            params![until, Utc::now().to_rfc3339(), task.id],
+        )?;
+        self.fetch_task(&conn, &task.id)
+    }
+
+    pub fn archive_task(&self, id: &str) -> Result<Task> {
+        self.update_status(id, "archived")
+    }
+
+    /// Archives a task and, when `cascade` is set, every subtask beneath it.
+    /// Returns the tasks whose status actually changed.
+    pub fn archive_task_cascade(&self, payload: ArchiveTaskCascade) -> Result<Vec<Task>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let root = self.fetch_task(&tx, &payload.id)?;
+
+        // Walk with an explicit stack so deep trees cannot overflow, and keep
+        // a visited set in case a corrupted parent chain loops back.
+        let mut ids = vec![root.id.clone()];
+        if payload.cascade {
+            let mut seen = BTreeSet::from([root.id.clone()]);
+            let mut pending = vec![root.id];
+            let mut children =
+                tx.prepare("SELECT id FROM tasks WHERE parent_id = ?1 ORDER BY created_at ASC")?;
+            while let Some(parent) = pending.pop() {
+                let found = children
+                    .query_map([&parent], |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                for child in found {
+                    if seen.insert(child.clone()) {
+                        ids.push(child.clone());
+                        pending.push(child);
+                    }
+                }
+            }
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut archived = Vec::new();
+        for id in ids {
+            let updated = tx.execute(
+                "UPDATE tasks SET status = 'archived', updated_at = ?1 WHERE id = ?2 AND status != 'archived'",
+                params![now, id],
+            )?;
+            if updated > 0 {
+                archived.push(self.fetch_task(&tx, &id)?);
+            }
+        }
+        tx.commit()?;
+        Ok(archived)
+    }
+
+    pub fn archive_matching(&self, query: &str) -> Result<Vec<String>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(DbError::InvalidInput(
+                "search query must not be empty".into(),
+            ));
+        }
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let ids = tx
+            .prepare(
+                "SELECT id FROM tasks WHERE status != 'archived' AND (name LIKE ?1 ESCAPE '\\' OR description LIKE ?1 ESCAPE '\\') ORDER BY created_at ASC",
+            )?
+            .query_map([like_pattern(query)], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let now = Utc::now().to_rfc3339();
+        for id in &ids {
+            tx.execute(
+                "UPDATE tasks SET status = 'archived', updated_at = ?1 WHERE id = ?2",
+                params![now, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    pub fn list_stale(&self, days: i64) -> Result<Vec<Task>> {
+        if days < 0 {
+            return Err(DbError::InvalidInput("days must not be negative".into()));
+        }
+        let age = Duration::try_days(days)
+            .ok_or_else(|| DbError::InvalidInput("days is too large".into()))?;
+        let cutoff = Utc::now() - age;
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE status = 'active' AND progress < target AND updated_at < ?1 ORDER BY updated_at ASC"
+        ))?;
+        let rows = stmt
+            .query_map([cutoff.to_rfc3339()], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    pub fn auto_archive_completed(&self, older_than_days: i64) -> Result<usize> {
+        if older_than_days < 0 {
+            return Err(DbError::InvalidInput(
+                "older_than_days must not be negative".into(),
+            ));
+        }
+        let age = Duration::try_days(older_than_days)
+            .ok_or_else(|| DbError::InvalidInput("older_than_days is too large".into()))?;
+        let now = Utc::now();
+        let cutoff = now - age;
+
+        let conn = self.conn()?;
+        let archived = conn.execute(
+            "UPDATE tasks SET status = 'archived', updated_at = ?1 WHERE status = 'completed' AND updated_at < ?2",
+            params![now.to_rfc3339(), cutoff.to_rfc3339()],
+        )?;
+        Ok(archived)
+    }
+
+    pub fn reopen_task(&self, id: &str) -> Result<Task> {
+        let task = self.update_status(id, "active")?;
+        if task.task_type == "cycle" {
+            let conn = self.conn()?;
+            conn.execute(
+                "UPDATE tasks SET progress = 0, updated_at = ?1 WHERE id = ?2",
+                params![Utc::now().to_rfc3339(), task.id],
+            )?;
+            return self.fetch_task(&conn, &task.id);
+        }
+        Ok(task)
+    }
+
+    pub fn resetting_today(&self) -> Result<Vec<Task>> {
+        let today = Local::now().date_naive();
+        let yesterday = today.pred_opt().unwrap_or(today);
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE task_type = 'cycle' AND status != 'archived' ORDER BY created_at ASC"
+        ))?;
+        let rows = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rows
+            .into_iter()
+            .filter(|task| {
+                let rule = task.repeat_rule.as_deref();
+                cycle_period(rule, today) != cycle_period(rule, yesterday)
+                    && local_date(&task.updated_at).is_some_and(|day| day < today)
+            })
+            .collect())
+    }
+
+    pub fn reset_cycle(&self, id: &str) -> Result<Task> {
+        let conn = self.conn()?;
+        let task = self.fetch_task(&conn, id)?;
+
+        if task.task_type != "cycle" {
+            return Err(DbError::InvalidInput(format!(
+                "task {id} is not a cycle task"
+            )));
+        }
+        if task.status == "archived" {
+            return Err(DbError::InvalidInput("cannot update archived task".into()));
+        }
+
+        conn.execute(
+            "UPDATE tasks SET progress = 0, status = 'active', updated_at = ?1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), task.id],
+        )?;
+        self.fetch_task(&conn, &task.id)
+    }
+
+    fn update_status(&self, id: &str, status: &str) -> Result<Task> {
+        let conn = self.conn()?;
+        let updated_at = Utc::now().to_rfc3339();
+        conn.execute(
+            "UPDATE tasks SET status = ?1, updated_at = ?2 WHERE id = ?3",
+            params![status, updated_at, id],
+        )?;
+        self.fetch_task(&conn, id)
+    }
+
+    pub fn export_report(&self, path: &Path) -> Result<()> {
+        let review = self.weekly_review()?;
+        let types = self.list_task_types()?;
+        let history = self.completion_history("week", 8)?;
+        crate::export::write_html_report(path, &review, &types, &history)
+    }
+
+    pub fn export_ics(&self, path: &Path) -> Result<usize> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE task_type = 'long_term' AND status = 'active' ORDER BY start_date ASC"
+        ))?;
+        let tasks = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        crate::export::write_ics(path, &tasks)
+    }
+
+    pub fn export_tasks(&self, path: &Path, filter: TaskQuery) -> Result<usize> {
+        let tasks = self.query_tasks(filter.clone())?;
+        let export = TaskExport {
+            exported_at: Utc::now().to_rfc3339(),
+            schema_version: SCHEMA_VERSION,
+            filter,
+            tasks,
+        };
+        crate::export::write_json(path, &export)?;
+        Ok(export.tasks.len())
+    }
+
+    pub fn diff_against_export(&self, path: &Path) -> Result<ExportDiff> {
+        let export: TaskExport = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let mut previous: BTreeMap<String, Task> = export
+            .tasks
+            .into_iter()
+            .map(|task| (task.id.clone(), task))
+            .collect();
+        let mut current = self.query_tasks(export.filter)?;
+        current.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut diff = ExportDiff::default();
+        for task in current {
+            let Some(old) = previous.remove(&task.id) else {
+                diff.added.push(task);
+                continue;
+            };
+            let fields = changed_fields(&old, &task)?;
+            if !fields.is_empty() {
+                diff.changed.push(ChangedTask { task, fields });
+            }
+        }
+        diff.removed = previous.into_values().collect();
+        Ok(diff)
+    }
+
+    pub fn export_tasks_ndjson(&self, path: &Path) -> Result<usize> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at ASC"
+        ))?;
+        let tasks = stmt
+            .query_map([], Task::from_row)?
+            .map(|task| task.map_err(DbError::from));
+        crate::export::write_ndjson(path, tasks)
+    }
+
+    pub fn export_task(&self, id: &str) -> Result<String> {
+        let conn = self.conn()?;
+        let task = self.fetch_task(&conn, id)?;
+        let date_range = match (task.start_date, task.end_date) {
+            (Some(start), Some(end)) => Some(vec![start, end]),
+            _ => None,
+        };
+        let template = TaskTemplate {
+            name: task.name,
+            description: task.description,
+            task_type: task.task_type,
+            target: task.target,
+            repeat: task.repeat_rule,
+            date_range,
+            color: task.color,
+            notes: task.notes,
+        };
+        Ok(serde_json::to_string_pretty(&template)?)
+    }
+
+    pub fn import_task(&self, json: &str) -> Result<Task> {
+        let template: TaskTemplate = serde_json::from_str(json)?;
+        self.create_task(NewTask {
+            id: None,
+            name: template.name,
+            description: Some(template.description),
+            task_type: template.task_type,
+            target: Some(template.target),
+            repeat: template.repeat,
+            date_range: template.date_range,
+            color: template.color,
+            notes: template.notes,
+            regenerate: None,
+            reminder_time: None,
+        })
+    }
+
+    pub fn import_tasks_csv(&self, path: &Path, partial: bool) -> Result<CsvImportReport> {
+        let mut records = crate::export::read_csv(path)?.into_iter();
+        let (_, header) = records
+            .next()
+            .ok_or_else(|| DbError::InvalidInput("csv file is empty".into()))?;
+        if header
+            .iter()
+            .map(|column| column.trim())
+            .ne(crate::export::CSV_COLUMNS)
+        {
+            return Err(DbError::InvalidInput(format!(
+                "csv header must be: {}",
+                crate::export::CSV_COLUMNS.join(",")
+            )));
+        }
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut report = CsvImportReport::default();
+        for (line, fields) in records {
+            if fields.iter().all(|field| field.trim().is_empty()) {
+                continue;
+            }
+            match import_csv_row(&tx, &fields) {
+                Ok(true) => report.updated += 1,
+                Ok(false) => report.created += 1,
+                Err(err) => report.errors.push(CsvRowError {
+                    line,
+                    message: err.to_string(),
+                }),
+            }
+        }
+
+        report.committed = partial || report.errors.is_empty();
+        if report.committed {
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+
+    pub fn schema_version(&self) -> Result<SchemaVersion> {
+        let conn = self.conn()?;
+        let schema_version = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(SchemaVersion {
+            schema_version,
+            sqlite_version: rusqlite::version().to_string(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
+    pub fn compact_database(&self) -> Result<CompactReport> {
+        let conn = self.conn()?;
+        if !conn.is_autocommit() {
+            return Err(DbError::InvalidInput(
+                "cannot compact while a transaction is open".into(),
+            ));
+        }
+
+        let path = self.file_path()?;
+        let size_before = std::fs::metadata(&path)?.len();
+        conn.execute_batch(
+            "VACUUM;
+            PRAGMA optimize;
+            PRAGMA wal_checkpoint(TRUNCATE);",
+        )?;
+        let size_after = std::fs::metadata(&path)?.len();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?2)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![SETTING_LAST_VACUUM_AT, now],
+        )?;
+
+        Ok(CompactReport {
+            size_before,
+            size_after,
+        })
+    }
+
+    pub fn db_stats(&self) -> Result<DbStats> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut tables = Vec::with_capacity(names.len());
+        for name in names {
+            let rows = conn.query_row(
+                &format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")),
+                [],
+                |row| row.get(0),
+            )?;
+            tables.push(TableCount { name, rows });
+        }
+        let last_vacuum_at = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                [SETTING_LAST_VACUUM_AT],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let path = self.path();
+        let file_size = path
+            .as_deref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len());
+        let wal_size = path
+            .as_deref()
+            .map(|path| {
+                let mut wal = path.as_os_str().to_owned();
+                wal.push("-wal");
+                PathBuf::from(wal)
+            })
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len());
+
+        Ok(DbStats {
+            tables,
+            file_size,
+            wal_size,
+            last_vacuum_at,
+        })
+    }
+
+    pub fn checkpoint_wal(&self) -> Result<WalCheckpoint> {
+        let conn = self.conn()?;
+        let checkpoint = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+            Ok(WalCheckpoint {
+                busy: row.get::<_, i64>(0)? != 0,
+                log_frames: row.get(1)?,
+                checkpointed_frames: row.get(2)?,
+            })
+        })?;
+        Ok(checkpoint)
+    }
+
+    pub fn find_invalid_ranges(&self) -> Result<Vec<Task>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE task_type = 'long_term' ORDER BY created_at ASC"
+        ))?;
+        let tasks = stmt
+            .query_map([], Task::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let parse = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        };
+        Ok(tasks
+            .into_iter()
+            .filter(
+                |task| match (parse(&task.start_date), parse(&task.end_date)) {
+                    (Some(start), Some(end)) => start > end,
+                    _ => true,
+                },
+            )
+            .collect())
+    }
+
+    pub fn upcoming_deadlines(&self, within_days: i64) -> Result<Vec<UpcomingDeadline>> {
+        if within_days < 0 {
+            return Err(DbError::InvalidInput(
+                "within_days must not be negative".into(),
+            ));
+        }
+        let window = Duration::try_days(within_days)
+            .ok_or_else(|| DbError::InvalidInput("within_days is too large".into()))?;
+        let today = Local::now().date_naive();
+        let until = today
+            .checked_add_signed(window)
+            .ok_or_else(|| DbError::InvalidInput("within_days is too large".into()))?;
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE task_type = 'long_term' AND status = 'active' AND end_date >= ?1 AND end_date <= ?2 ORDER BY end_date ASC"
+        ))?;
+        let tasks = stmt
+            .query_map(
+                params![
+                    today.format("%Y-%m-%d").to_string(),
+                    until.format("%Y-%m-%d").to_string()
+                ],
+                Task::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(tasks
+            .into_iter()
+            .filter_map(|task| {
+                let end = NaiveDate::parse_from_str(task.end_date.as_deref()?, "%Y-%m-%d").ok()?;
+                Some(UpcomingDeadline {
+                    days_remaining: (end - today).num_days(),
+                    task,
+                })
+            })
+            .collect())
+    }
+
+    pub fn progress_timeline(&self, id: &str) -> Result<Vec<ProgressPoint>> {
+        let task = self.get_task(id)?;
+        if task.updated_at == task.created_at && task.progress == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(vec![
+            ProgressPoint {
+                timestamp: task.created_at,
+                progress: 0,
+            },
+            ProgressPoint {
+                timestamp: task.updated_at,
+                progress: task.progress,
+            },
+        ])
+    }
+
+    /// Only cycle tasks have a habit age; other task types are rejected.
+    pub fn habit_age(&self, id: &str) -> Result<HabitAge> {
+        let conn = self.conn()?;
+        let task = self.fetch_task(&conn, id)?;
+        if task.task_type != "cycle" {
+            return Err(DbError::InvalidInput(format!(
+                "task {id} is not a cycle task"
+            )));
+        }
+
+        let created_at = DateTime::parse_from_rfc3339(&task.created_at)
+            .map_err(|_| DbError::InvalidInput(format!("task {id} has an invalid created_at")))?
+            .with_timezone(&Utc);
+        let (streak, last_completed_at): (i64, Option<String>) = conn.query_row(
+            "SELECT streak, last_completed_at FROM tasks WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(HabitAge {
+            days_tracked: (Utc::now() - created_at).num_days().max(0),
+            streak: current_streak(
+                task.repeat_rule.as_deref(),
+                streak,
+                last_completed_at.as_deref(),
+                task.frozen_until.as_deref(),
+                Local::now().date_naive(),
+            ),
+            id: task.id,
+        })
+    }
+
+    pub fn project_completion(&self, id: &str) -> Result<CompletionProjection> {
+        let conn = self.conn()?;
+        let task = self.fetch_task(&conn, id)?;
+        if task.task_type != "long_term" {
+            return Err(DbError::InvalidInput(format!(
+                "task {id} is not a long term task"
+            )));
+        }
+
+        let now = Utc::now();
+        let created_at = DateTime::parse_from_rfc3339(&task.created_at)
+            .map_err(|_| DbError::InvalidInput(format!("task {id} has an invalid created_at")))?
+            .with_timezone(&Utc);
+        let elapsed_days = ((now - created_at).num_seconds() as f64 / 86_400.0).max(1.0);
+        let daily_rate = task.progress as f64 / elapsed_days;
+
+        let projected = if task.progress >= task.target {
+            Some(now)
+        } else if task.progress > 0 {
+            let remaining_days = (task.target - task.progress) as f64 / daily_rate;
+            Duration::try_seconds((remaining_days * 86_400.0).ceil() as i64)
+                .and_then(|remaining| now.checked_add_signed(remaining))
+        } else {
+            None
+        };
+
+        let end_date = task
+            .end_date
+            .as_deref()
+            .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok());
+        let beats_end_date = match (projected, end_date) {
+            (Some(projected), Some(end)) => {
+                Some(projected.with_timezone(&Local).date_naive() <= end)
+            }
+            _ => None,
+        };
+
+        Ok(CompletionProjection {
+            id: task.id,
+            daily_rate: (daily_rate * 100.0).round() / 100.0,
+            projected_completion: projected.map(|value| value.to_rfc3339()),
+            beats_end_date,
+        })
+    }
+
+    pub fn completion_history(&self, bucket: &str, periods: i64) -> Result<Vec<HistoryBucket>> {
+        if !(1..=520).contains(&periods) {
+            return Err(DbError::InvalidInput(
+                "periods must be between 1 and 520".into(),
+            ));
+        }
+        let today = Local::now().date_naive();
+        let (bucket_expr, starts): (&str, Vec<NaiveDate>) = match bucket {
+            "week" => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (
+                    "date(updated_at, 'localtime', '-6 days', 'weekday 1')",
+                    (0..periods)
+                        .rev()
+                        .map(|offset| monday - Duration::weeks(offset))
+                        .collect(),
+                )
+            }
+            "month" => {
+                let first = today.with_day(1).unwrap_or(today);
+                (
+                    "strftime('%Y-%m-01', updated_at, 'localtime')",
+                    (0..periods)
+                        .rev()
+                        .filter_map(|offset| first.checked_sub_months(Months::new(offset as u32)))
+                        .collect(),
+                )
+            }
+            other => {
+                return Err(DbError::InvalidInput(format!(
+                    "bucket must be week or month: {other}"
+                )))
+            }
+        };
+
+        let earliest = starts.first().copied().unwrap_or(today);
+        let cutoff = local_day_start(earliest);
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {bucket_expr} AS bucket, COUNT(*) FROM tasks WHERE status = 'completed' AND updated_at >= ?1 GROUP BY bucket"
+        ))?;
+        let counts = stmt
+            .query_map([cutoff.to_rfc3339()], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+
+        Ok(starts
+            .into_iter()
+            .map(|start| {
+                let start = start.format("%Y-%m-%d").to_string();
+                let count = counts.get(&start).copied().unwrap_or(0);
+                HistoryBucket { start, count }
+            })
+            .collect())
+    }
+
+    pub fn velocity_trend(&self, weeks: i64) -> Result<VelocityTrend> {
+        let buckets = self.completion_history("week", weeks)?;
+        let slope = if buckets.len() < 2 {
+            0.0
+        } else {
+            let n = buckets.len() as f64;
+            let mean_x = (n - 1.0) / 2.0;
+            let mean_y = buckets.iter().map(|b| b.count as f64).sum::<f64>() / n;
+            let (covariance, variance) = buckets.iter().enumerate().fold(
+                (0.0, 0.0),
+                |(covariance, variance), (x, bucket)| {
+                    let dx = x as f64 - mean_x;
+                    (
+                        covariance + dx * (bucket.count as f64 - mean_y),
+                        variance + dx * dx,
+                    )
+                },
+            );
+            covariance / variance
+        };
+        let trend = if slope > TREND_THRESHOLD {
+            "rising"
+        } else if slope < -TREND_THRESHOLD {
+            "falling"
+        } else {
+            "steady"
+        };
+        Ok(VelocityTrend {
+            weeks: buckets,
+            slope: (slope * 100.0).round() / 100.0,
+            trend: trend.to_string(),
+        })
+    }
+
+    pub fn creation_histogram(&self, months: i64) -> Result<Vec<MonthCount>> {
+        if !(1..=240).contains(&months) {
+            return Err(DbError::InvalidInput(
+                "months must be between 1 and 240".into(),
+            ));
+        }
+        let today = Local::now().date_naive();
+        let first = today.with_day(1).unwrap_or(today);
+        let starts = (0..months)
+            .rev()
+            .filter_map(|offset| first.checked_sub_months(Months::new(offset as u32)))
+            .collect::<Vec<_>>();
+        let cutoff = local_day_start(starts.first().copied().unwrap_or(first));
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT strftime('%Y-%m', created_at, 'localtime') AS month, COUNT(*) FROM tasks WHERE created_at >= ?1 GROUP BY month",
+        )?;
+        let counts = stmt
+            .query_map([cutoff.to_rfc3339()], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+
+        Ok(starts
+            .into_iter()
+            .map(|start| {
+                let month = start.format("%Y-%m").to_string();
+                let count = counts.get(&month).copied().unwrap_or(0);
+                MonthCount { month, count }
+            })
+            .collect())
+    }
+
+    pub fn list_task_types(&self) -> Result<Vec<TaskTypeCount>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT task_type, COUNT(*) FROM tasks GROUP BY task_type ORDER BY COUNT(*) DESC, task_type ASC",
+        )?;
+        let types = stmt
+            .query_map([], |row| {
+                Ok(TaskTypeCount {
+                    task_type: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(types)
+    }
+
+    pub fn group_progress(&self, filter: GroupFilter) -> Result<GroupProgress> {
+        let (clause, value) = match (filter.task_type, filter.tag) {
+            (Some(task_type), None) => {
+                validate_task_type(&task_type)?;
+                ("task_type = ?1", task_type)
+            }
+            (None, Some(tag)) => (
+                "id IN (SELECT task_id FROM task_tags WHERE tag = ?1)",
+                normalize_tag(&tag)?,
+            ),
+            _ => {
+                return Err(DbError::InvalidInput(
+                    "group filter needs exactly one of type or tag".into(),
+                ))
+            }
+        };
+        let conn = self.conn()?;
+        let (count, progress, target): (i64, i64, i64) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(progress), 0), COALESCE(SUM(target), 0)
+                FROM tasks WHERE status = 'active' AND {clause}"
+            ),
+            [&value],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok(GroupProgress {
+            count,
+            progress,
+            target,
+            ratio: if target > 0 {
+                progress as f64 / target as f64
+            } else {
+                0.0
+            },
+        })
+    }
+
+    pub fn find_duplicates(&self) -> Result<Vec<DuplicateGroup>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name FROM tasks WHERE status = 'active' ORDER BY created_at ASC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (id, name) in rows {
+            groups
+                .entry(name.trim().to_lowercase())
+                .or_default()
+                .push(id);
+        }
+        Ok(groups
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(name, ids)| DuplicateGroup { name, ids })
+            .collect())
+    }
+
+    pub fn weekly_review(&self) -> Result<WeeklyReview> {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let week_end = week_start + Duration::days(6);
+        let since = week_start.and_time(NaiveTime::MIN).and_utc().to_rfc3339();
+
+        let tasks = {
+            let conn = self.conn()?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks WHERE status != 'archived' ORDER BY updated_at DESC"
+            ))?;
+            let rows = stmt
+                .query_map([], Task::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+
+        let today_text = today.format("%Y-%m-%d").to_string();
+        let mut review = WeeklyReview {
+            week_start: week_start.format("%Y-%m-%d").to_string(),
+            week_end: week_end.format("%Y-%m-%d").to_string(),
+            completed: Vec::new(),
+            active: Vec::new(),
+            overdue: Vec::new(),
+            streaks: self.top_streaks(5)?,
+        };
+        for task in tasks {
+            if task.status == "completed" {
+                if task.updated_at >= since {
+                    review.completed.push(task);
+                }
+                continue;
+            }
+            if task.task_type == "long_term"
+                && task
+                    .end_date
+                    .as_deref()
+                    .is_some_and(|end| end < today_text.as_str())
+            {
+                review.overdue.push(task.clone());
+            }
+            review.active.push(task);
+        }
+        Ok(review)
+    }
+
+    pub fn top_streaks(&self, limit: i64) -> Result<Vec<StreakEntry>> {
+        if limit < 1 {
+            return Err(DbError::InvalidInput("limit must be at least 1".into()));
+        }
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, repeat_rule, streak, last_completed_at, frozen_until FROM tasks WHERE task_type = 'cycle' AND status != 'archived'",
+        )?;
+        let today = Local::now().date_naive();
+        let mut entries = stmt
+            .query_map([], |row| {
+                let repeat_rule: Option<String> = row.get(2)?;
+                let streak: i64 = row.get(3)?;
+                let last_completed_at: Option<String> = row.get(4)?;
+                let frozen_until: Option<String> = row.get(5)?;
+                Ok(StreakEntry {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    streak: current_streak(
+                        repeat_rule.as_deref(),
+                        streak,
+                        last_completed_at.as_deref(),
+                        frozen_until.as_deref(),
+                        today,
+                    ),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        entries.sort_by(|a, b| b.streak.cmp(&a.streak).then_with(|| a.name.cmp(&b.name)));
+        entries.truncate(limit as usize);
+        Ok(entries)
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.conn()?;
+        let value = conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(DbError::InvalidInput(
+                "setting key must not be empty".into(),
+            ));
+        }
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn dock_side(&self) -> Result<Option<String>> {
+        self.get_setting(SETTING_DOCK_SIDE)
+    }
+
+    pub fn set_dock_side(&self, side: &str) -> Result<()> {
+        self.set_setting(SETTING_DOCK_SIDE, side)
+    }
+
+    pub fn panel_mode(&self) -> Result<Option<String>> {
+        self.get_setting(SETTING_PANEL_MODE)
+    }
+
+    pub fn set_panel_mode(&self, mode: &str) -> Result<()> {
+        self.set_setting(SETTING_PANEL_MODE, mode)
+    }
+
+    pub fn taskbar_offset(&self) -> Result<f64> {
+        Ok(self
+            .get_setting(SETTING_TASKBAR_OFFSET)?
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|offset| (0.0..=MAX_TASKBAR_OFFSET).contains(offset))
+            .unwrap_or(DEFAULT_TASKBAR_OFFSET))
+    }
+
+    pub fn set_taskbar_offset(&self, pixels: f64) -> Result<()> {
+        if !(0.0..=MAX_TASKBAR_OFFSET).contains(&pixels) {
+            return Err(DbError::InvalidInput(format!(
+                "taskbar offset must be between 0 and {MAX_TASKBAR_OFFSET} pixels"
+            )));
+        }
+        self.set_setting(SETTING_TASKBAR_OFFSET, &pixels.to_string())
+    }
+
+    pub fn active_task_limit(&self) -> Result<i64> {
+        Ok(self
+            .get_setting(SETTING_ACTIVE_TASK_LIMIT)?
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|limit| *limit > 0)
+            .unwrap_or(DEFAULT_ACTIVE_TASK_LIMIT))
+    }
+
+    pub fn count_active_tasks(&self) -> Result<i64> {
+        let conn = self.conn()?;
+        let active = conn.query_row(
+            "SELECT COUNT(*) FROM tasks WHERE status = 'active'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(active)
+    }
+
+    pub fn active_task_warning(&self) -> Result<Option<ActiveTaskWarning>> {
+        let limit = self.active_task_limit()?;
+        let active = self.count_active_tasks()?;
+        Ok((active > limit).then_some(ActiveTaskWarning { active, limit }))
+    }
+
+    pub fn toggle_shortcut(&self) -> Result<String> {
+        Ok(self
+            .get_setting(SETTING_TOGGLE_SHORTCUT)?
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_TOGGLE_SHORTCUT.to_string()))
+    }
+
+    pub fn quick_add_shortcut(&self) -> Result<String> {
+        Ok(self
+            .get_setting(SETTING_QUICK_ADD_SHORTCUT)?
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_QUICK_ADD_SHORTCUT.to_string()))
+    }
+
+    pub fn do_not_disturb(&self) -> Result<DoNotDisturb> {
+        let Some(value) = self.get_setting(SETTING_DND_UNTIL)? else {
+            return Ok(DoNotDisturb::default());
+        };
+        if value == DND_INDEFINITE {
+            return Ok(DoNotDisturb {
+                active: true,
+                until: None,
+            });
+        }
+        let expired = DateTime::parse_from_rfc3339(&value)
+            .map(|until| until <= Utc::now())
+            .unwrap_or(true);
+        if expired {
+            self.clear_do_not_disturb()?;
+            return Ok(DoNotDisturb::default());
+        }
+        Ok(DoNotDisturb {
+            active: true,
+            until: Some(value),
+        })
+    }
+
+    pub fn set_do_not_disturb(&self, until: Option<&str>) -> Result<DoNotDisturb> {
+        let value = match until.map(str::trim) {
+            None | Some("") => DND_INDEFINITE.to_string(),
+            Some(value) => {
+                let until = DateTime::parse_from_rfc3339(value)
+                    .map_err(|_| DbError::InvalidInput(format!("invalid timestamp: {value}")))?
+                    .with_timezone(&Utc);
+                if until <= Utc::now() {
+                    return Err(DbError::InvalidInput(
+                        "do-not-disturb end must be in the future".into(),
+                    ));
+                }
+                until.to_rfc3339()
+            }
+        };
+        self.set_setting(SETTING_DND_UNTIL, &value)?;
+        self.do_not_disturb()
+    }
+
+    pub fn clear_do_not_disturb(&self) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM settings WHERE key = ?1", [SETTING_DND_UNTIL])?;
+        Ok(())
+    }
+
+    pub fn log_level(&self) -> Result<Option<String>> {
+        self.get_setting(SETTING_LOG_LEVEL)
+    }
+
+    pub fn set_log_level(&self, level: &str) -> Result<()> {
+        self.set_setting(SETTING_LOG_LEVEL, level)
+    }
+
+    pub fn window_geometry(&self, monitor: &str) -> Result<Option<WindowGeometry>> {
+        self.get_setting(&format!("{SETTING_WINDOW_GEOMETRY_PREFIX}{monitor}"))?
+            .map(|value| serde_json::from_str(&value).map_err(DbError::from))
+            .transpose()
+    }
+
+    pub fn set_window_geometry(&self, monitor: &str, geometry: &WindowGeometry) -> Result<()> {
+        self.set_setting(
+            &format!("{SETTING_WINDOW_GEOMETRY_PREFIX}{monitor}"),
+            &serde_json::to_string(geometry)?,
+        )
+    }
+
+    fn fetch_task(&self, conn: &rusqlite::Connection, id: &str) -> Result<Task> {
+        conn.query_row(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?1"),
+            [id],
+            Task::from_row,
+        )
+        .optional()
+        .map_err(DbError::from)?
+        .ok_or_else(|| DbError::NotFound(format!("task {id}")))
+    }
+}
+
+fn probe_writable(db_path: &Path) -> Result<()> {
+    let probe = rusqlite::Connection::open(db_path)
+        .and_then(|conn| conn.execute_batch("BEGIN IMMEDIATE; COMMIT;"));
+    match probe {
+        Err(rusqlite::Error::SqliteFailure(failure, _))
+            if matches!(
+                failure.code,
+                ErrorCode::ReadOnly | ErrorCode::CannotOpen | ErrorCode::PermissionDenied
+            ) =>
+        {
+            let dir = db_path.parent().unwrap_or(db_path);
+            Err(read_only_storage(dir, &failure))
+        }
+        Err(err) => Err(err.into()),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn create_storage_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|err| match err.kind() {
+        ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => read_only_storage(dir, &err),
+        _ => DbError::Io(err),
+    })
+}
+
+fn read_only_storage(dir: &Path, err: &dyn std::fmt::Display) -> DbError {
+    DbError::AppDir(format!(
+        "storage directory {} is not writable ({err}); check its permissions or free up space and restart TimeMaster",
+        dir.display()
+    ))
+}
+
+fn read_query(conn: &rusqlite::Connection, sql: &str) -> Result<QueryResult> {
+    let mut stmt = conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err(DbError::InvalidInput(
+            "only read-only queries may be run".into(),
+        ));
+    }
+    let columns = stmt
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let mut rows = stmt.query([])?;
+    let mut result = QueryResult {
+        columns,
+        rows: Vec::new(),
+        truncated: false,
+    };
+    while let Some(row) = rows.next()? {
+        if result.rows.len() == MAX_QUERY_ROWS {
+            result.truncated = true;
+            break;
+        }
+        let mut object = serde_json::Map::new();
+        for (index, column) in result.columns.iter().enumerate() {
+            let value = match row.get_ref(index)? {
+                ValueRef::Null => serde_json::Value::Null,
+                ValueRef::Integer(value) => value.into(),
+                ValueRef::Real(value) => value.into(),
+                ValueRef::Text(value) => String::from_utf8_lossy(value).into_owned().into(),
+                ValueRef::Blob(value) => value
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>()
+                    .into(),
+            };
+            object.insert(column.clone(), value);
+        }
+        result.rows.push(object);
+    }
+    Ok(result)
+}
+
+fn daily_cap(conn: &rusqlite::Connection, task_type: &str) -> Result<Option<i64>> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            [format!("{SETTING_DAILY_CAP_PREFIX}{task_type}")],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value.and_then(|value| value.trim().parse::<i64>().ok()))
+}
+
+fn enforce_daily_cap(conn: &rusqlite::Connection, task_type: &str) -> Result<()> {
+    let Some(cap) = daily_cap(conn, task_type)? else {
+        return Ok(());
+    };
+    let since = local_day_start(Local::now().date_naive()).to_rfc3339();
+    let created: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM tasks WHERE task_type = ?1 AND created_at >= ?2",
+        params![task_type, since],
+        |row| row.get(0),
+    )?;
+    if created >= cap {
+        return Err(DbError::PolicyViolation(format!(
+            "at most {cap} new {task_type} tasks may be created per day"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().count() > MAX_PROFILE_NAME_LENGTH {
+        return Err(DbError::InvalidInput(format!(
+            "profile name must be 1 to {MAX_PROFILE_NAME_LENGTH} characters"
+        )));
+    }
+    if !name
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    {
+        return Err(DbError::InvalidInput(format!(
+            "profile name may only contain letters, digits, '-' and '_': {name}"
+        )));
+    }
+    Ok(())
+}
+
+fn profile_file(name: &str) -> String {
+    if name == DEFAULT_PROFILE {
+        DATABASE_FILE.to_string()
+    } else {
+        format!("timemaster-{name}.db")
+    }
+}
+
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        log::info!("adding column {table}.{column}");
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn strip_control_chars(value: &str) -> String {
+    value.chars().filter(|ch| !ch.is_control()).collect()
+}
+
+fn normalize_name(name: &str) -> Result<String> {
+    let name = strip_control_chars(name).trim().to_string();
+    if name.is_empty() {
+        return Err(DbError::InvalidInput("task name must not be empty".into()));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(DbError::InvalidInput(format!(
+            "task name must be at most {MAX_NAME_LENGTH} characters"
+        )));
+    }
+    Ok(name)
+}
+
+fn normalize_description(description: &str) -> Result<String> {
+    let description = strip_control_chars(description).trim().to_string();
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(DbError::InvalidInput(format!(
+            "description must be at most {MAX_DESCRIPTION_LENGTH} characters"
+        )));
+    }
+    Ok(description)
+}
+
+fn normalize_notes(notes: Option<String>) -> Option<String> {
+    notes.filter(|notes| !notes.trim().is_empty())
+}
+
+fn normalize_color(color: Option<String>) -> Result<Option<String>> {
+    let Some(color) = color
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+    else {
+        return Ok(None);
+    };
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|ch| ch.is_ascii_hexdigit());
+    if valid {
+        Ok(Some(color))
+    } else {
+        Err(DbError::InvalidInput(format!(
+            "color must be a #RRGGBB hex string: {color}"
+        )))
+    }
+}
+
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = strip_control_chars(tag).trim().to_string();
+    if tag.is_empty() {
+        return Err(DbError::InvalidInput("tag must not be empty".into()));
+    }
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(DbError::InvalidInput(format!(
+            "tag must be at most {MAX_TAG_LENGTH} characters"
+        )));
+    }
+    Ok(tag)
+}
+
+fn find_orphans(conn: &rusqlite::Connection) -> Result<Vec<Task>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM tasks WHERE status != 'archived') ORDER BY created_at ASC"
+    ))?;
+    let rows = stmt
+        .query_map([], Task::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+/// Rejects a parent that is missing, archived, or would close a cycle by
+/// being `id` itself or one of its descendants.
+fn validate_parent(conn: &rusqlite::Connection, id: &str, parent_id: &str) -> Result<()> {
+    let status: Option<String> = conn
+        .query_row(
+            "SELECT status FROM tasks WHERE id = ?1",
+            [parent_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match status.as_deref() {
+        None => return Err(DbError::NotFound(format!("task {parent_id}"))),
+        Some("archived") => {
+            return Err(DbError::InvalidInput(
+                "cannot nest a task under an archived task".into(),
+            ))
+        }
+        Some(_) => {}
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut current = Some(parent_id.to_string());
+    while let Some(ancestor) = current {
+        if ancestor == id {
+            return Err(DbError::InvalidInput(
+                "a task cannot be nested under itself or its subtasks".into(),
+            ));
+        }
+        if !seen.insert(ancestor.clone()) {
+            break;
+        }
+        current = conn
+            .query_row(
+                "SELECT parent_id FROM tasks WHERE id = ?1",
+                [&ancestor],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+    }
+    Ok(())
+}
+
+fn normalize_reminder_time(time: Option<String>) -> Result<Option<String>> {
+    let Some(time) = time
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(None);
+    };
+    let valid = time.len() == 5 && time.as_bytes()[2] == b':';
+    match NaiveTime::parse_from_str(&time, "%H:%M") {
+        Ok(parsed) if valid => Ok(Some(parsed.format("%H:%M").to_string())),
+        _ => Err(DbError::InvalidInput(format!(
+            "reminder time must be HH:MM between 00:00 and 23:59: {time}"
+        ))),
+    }
+}
+
+fn validate_priority(priority: i64) -> Result<()> {
+    if (0..=MAX_PRIORITY).contains(&priority) {
+        Ok(())
+    } else {
+        Err(DbError::InvalidInput(format!(
+            "priority must be between 0 and {MAX_PRIORITY}"
+        )))
+    }
+}
+
+fn normalize_regenerate(task_type: &str, regenerate: Option<String>) -> Result<Option<String>> {
+    let Some(rule) = regenerate.filter(|rule| !rule.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if task_type != "long_term" {
+        return Ok(None);
+    }
+    let rule = rule.trim();
+    if REGENERATE_RULES.contains(&rule) {
+        Ok(Some(rule.to_string()))
+    } else {
+        Err(DbError::InvalidInput(format!(
+            "unknown regenerate rule: {rule}"
+        )))
+    }
+}
+
+fn shift_by_rule(date: &str, rule: &str) -> Option<String> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let shifted = match rule {
+        "weekly" => date.checked_add_signed(Duration::days(7)),
+        "monthly" => date.checked_add_months(Months::new(1)),
+        "quarterly" => date.checked_add_months(Months::new(3)),
+        "yearly" => date.checked_add_months(Months::new(12)),
+        _ => None,
+    }?;
+    Some(shifted.format("%Y-%m-%d").to_string())
+}
+
+// The completed task hands its rule over to the new instance, so completing or
+// reopening it again can never spawn a second copy.
+fn regenerate_task(conn: &rusqlite::Connection, task: &Task) -> Result<Option<String>> {
+    if task.task_type != "long_term" {
+        return Ok(None);
+    }
+    let (Some(rule), Some(start), Some(end)) = (
+        task.regenerate_rule.as_deref(),
+        task.start_date.as_deref(),
+        task.end_date.as_deref(),
+    ) else {
+        return Ok(None);
+    };
+    let (Some(start), Some(end)) = (shift_by_rule(start, rule), shift_by_rule(end, rule)) else {
+        return Ok(None);
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO tasks (id, name, description, task_type, progress, target, start_date, end_date, status, created_at, updated_at, color, notes, parent_id, priority, regenerate_rule)
+        VALUES (?1, ?2, ?3, 'long_term', 0, ?4, ?5, ?6, 'active', ?7, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            id,
+            task.name,
+            task.description,
+            task.target,
+            start,
+            end,
+            now,
+            task.color,
+            task.notes,
+            task.parent_id,
+            task.priority,
+            rule
+        ],
+    )?;
+    conn.execute(
+        "INSERT INTO task_tags (task_id, tag) SELECT ?1, tag FROM task_tags WHERE task_id = ?2",
+        params![id, task.id],
+    )?;
+    conn.execute(
+        "UPDATE tasks SET regenerate_rule = NULL WHERE id = ?1",
+        [&task.id],
+    )?;
+    Ok(Some(id))
+}
+
+fn validate_status(status: &str) -> Result<()> {
+    if TASK_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(DbError::InvalidInput(format!("unknown status: {status}")))
+    }
+}
+
+fn validate_target(target: i64) -> Result<()> {
+    if target < 1 {
+        Err(DbError::InvalidInput("target must be at least 1".into()))
+    } else if target > MAX_TASK_TARGET {
+        Err(DbError::InvalidInput(format!(
+            "target must be at most {MAX_TASK_TARGET}"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_task_type(task_type: &str) -> Result<()> {
+    if TASK_TYPES.contains(&task_type) {
+        Ok(())
+    } else {
+        Err(DbError::InvalidInput(format!(
+            "unknown task type: {task_type}"
+        )))
+    }
+}
+
+fn normalize_schedule(
+    task_type: &str,
+    repeat: Option<String>,
+    date_range: Option<Vec<String>>,
 ) -> Result<(Option<String>, Option<String>, Option<String>)> {
     match task_type {
         "cycle" => Ok((repeat.filter(|r| !r.is_empty()), None, None)),
@@ -274,6 +3124,21 @@ fn normalize_schedule(
     }
 }
 
+fn parse_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| DbError::InvalidInput(format!("invalid date: {value}")))?;
+    let time = if end_of_day {
+        NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap_or(NaiveTime::MIN)
+    } else {
+        NaiveTime::MIN
+    };
+    Ok(date.and_time(time).and_utc())
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Task {
@@ -284,6 +3149,8 @@ pub struct Task {
     pub task_type: String,
     pub progress: i64,
     pub target: i64,
+    #[serde(default)]
+    pub progress_percent: f64,
     #[serde(rename = "repeatRule")]
     pub repeat_rule: Option<String>,
     #[serde(rename = "startDate")]
@@ -295,25 +3162,728 @@ pub struct Task {
     pub created_at: String,
     #[serde(rename = "updatedAt")]
     pub updated_at: String,
+    pub color: Option<String>,
+    pub snoozed_until: Option<String>,
+    pub frozen_until: Option<String>,
+    pub notes: Option<String>,
+    pub reminder_time: Option<String>,
+    pub regenerate_rule: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl Task {
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let progress: i64 = row.get(4)?;
+        let target: i64 = row.get(5)?;
+        let mut tags: Vec<String> = row
+            .get::<_, Option<String>>("tags")?
+            .map(|joined| joined.split('\u{1f}').map(str::to_string).collect())
+            .unwrap_or_default();
+        tags.sort();
         Ok(Self {
             id: row.get(0)?,
             name: row.get(1)?,
             description: row.get(2)?,
             task_type: row.get(3)?,
-            progress: row.get(4)?,
-            target: row.get(5)?,
+            progress,
+            target,
+            progress_percent: progress_percent(progress, target),
             repeat_rule: row.get(6)?,
             start_date: row.get(7)?,
             end_date: row.get(8)?,
             status: row.get(9)?,
             created_at: row.get(10)?,
             updated_at: row.get(11)?,
+            color: row.get(12)?,
+            snoozed_until: row.get(13)?,
+            frozen_until: row.get(14)?,
+            notes: row.get(15)?,
+            parent_id: row.get(16)?,
+            reminder_time: row.get(17)?,
+            priority: row.get(18)?,
+            regenerate_rule: row.get(19)?,
+            tags,
+        })
+    }
+}
+
+fn push_time_bounds(
+    clauses: &mut Vec<String>,
+    values: &mut Vec<Value>,
+    column: &str,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<()> {
+    let from = from
+        .as_deref()
+        .map(|value| parse_bound(value, false))
+        .transpose()?;
+    let to = to
+        .as_deref()
+        .map(|value| parse_bound(value, true))
+        .transpose()?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(DbError::InvalidInput(format!(
+                "{column} range start must not be after range end"
+            )));
+        }
+    }
+    if let Some(from) = from {
+        clauses.push(format!("{column} >= ?"));
+        values.push(Value::Text(from.to_rfc3339()));
+    }
+    if let Some(to) = to {
+        clauses.push(format!("{column} <= ?"));
+        values.push(Value::Text(to.to_rfc3339()));
+    }
+    Ok(())
+}
+
+fn record_deletion(conn: &rusqlite::Connection, id: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO deleted_tasks (id, deleted_at) VALUES (?1, ?2)",
+        params![id, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+fn clear_expired_snoozes(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE tasks SET snoozed_until = NULL WHERE snoozed_until IS NOT NULL AND snoozed_until <= ?1",
+        [Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+fn clear_expired_freezes(conn: &rusqlite::Connection) -> Result<()> {
+    let today = Local::now().date_naive();
+    let mut stmt = conn.prepare(
+        "SELECT id, repeat_rule, frozen_until FROM tasks WHERE frozen_until IS NOT NULL",
+    )?;
+    let expired = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, repeat_rule, frozen_until)| {
+            let rule = repeat_rule.as_deref();
+            frozen_period(rule, Some(frozen_until))
+                .is_none_or(|period| period < cycle_period(rule, today) - 1)
+        })
+        .map(|(id, _, _)| id)
+        .collect::<Vec<_>>();
+
+    for id in expired {
+        conn.execute("UPDATE tasks SET frozen_until = NULL WHERE id = ?1", [id])?;
+    }
+    Ok(())
+}
+
+fn entry_seconds(started_at: &str, ended_at: Option<&str>) -> i64 {
+    let parse = |value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|value| value.with_timezone(&Utc))
+    };
+    let (Some(start), Some(end)) = (parse(started_at), ended_at.map_or(Some(Utc::now()), parse))
+    else {
+        return 0;
+    };
+    (end - start).num_seconds().max(0)
+}
+
+fn local_day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|value| value.with_timezone(&Utc))
+        .unwrap_or_else(|| day.and_time(NaiveTime::MIN).and_utc())
+}
+
+fn local_date(timestamp: &str) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|value| value.with_timezone(&Local).date_naive())
+}
+
+fn date_range_contains(task: &Task, day: NaiveDate) -> bool {
+    let parse = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+    };
+    match (parse(&task.start_date), parse(&task.end_date)) {
+        (Some(start), Some(end)) => start <= day && day <= end,
+        _ => false,
+    }
+}
+
+fn cycle_period(repeat_rule: Option<&str>, day: NaiveDate) -> i64 {
+    match repeat_rule {
+        Some("weekly") => {
+            let monday = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+            monday.num_days_from_ce() as i64 / 7
+        }
+        Some("monthly") => day.year() as i64 * 12 + day.month0() as i64,
+        _ => day.num_days_from_ce() as i64,
+    }
+}
+
+// The original previous completion is not kept, so a rolled-back streak is
+// re-anchored one period back to let the next completion continue it.
+fn rollback_cycle_completion(
+    conn: &rusqlite::Connection,
+    task: &Task,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let (streak, last_completed_at): (i64, Option<String>) = conn.query_row(
+        "SELECT streak, last_completed_at FROM tasks WHERE id = ?1",
+        [&task.id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let rule = task.repeat_rule.as_deref();
+    let today = now.with_timezone(&Local).date_naive();
+    let last = last_completed_at
+        .as_deref()
+        .and_then(local_date)
+        .map(|day| cycle_period(rule, day));
+    if last != Some(cycle_period(rule, today)) {
+        return Ok(());
+    }
+
+    let streak = (streak - 1).max(0);
+    let anchor = if streak == 0 {
+        None
+    } else {
+        match rule {
+            Some("weekly") => now.checked_sub_signed(Duration::days(7)),
+            Some("monthly") => now.checked_sub_months(Months::new(1)),
+            _ => now.checked_sub_signed(Duration::days(1)),
+        }
+        .map(|anchor| anchor.to_rfc3339())
+    };
+    conn.execute(
+        "UPDATE tasks SET streak = ?1, last_completed_at = ?2 WHERE id = ?3",
+        params![streak, anchor, task.id],
+    )?;
+    Ok(())
+}
+
+fn frozen_period(repeat_rule: Option<&str>, frozen_until: Option<&str>) -> Option<i64> {
+    frozen_until
+        .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        .map(|day| cycle_period(repeat_rule, day))
+}
+
+fn streak_anchor(
+    repeat_rule: Option<&str>,
+    last_completed_at: Option<&str>,
+    frozen_until: Option<&str>,
+) -> Option<i64> {
+    let last = last_completed_at
+        .and_then(local_date)
+        .map(|day| cycle_period(repeat_rule, day));
+    last.max(frozen_period(repeat_rule, frozen_until))
+}
+
+fn current_streak(
+    repeat_rule: Option<&str>,
+    streak: i64,
+    last_completed_at: Option<&str>,
+    frozen_until: Option<&str>,
+    today: NaiveDate,
+) -> i64 {
+    let current = cycle_period(repeat_rule, today);
+    match streak_anchor(repeat_rule, last_completed_at, frozen_until) {
+        Some(anchor) if anchor >= current - 1 => streak,
+        _ => 0,
+    }
+}
+
+fn backfill_streaks(conn: &rusqlite::Connection) -> Result<usize> {
+    let updated = conn.execute(
+        "UPDATE tasks SET streak = 1, last_completed_at = updated_at
+        WHERE task_type = 'cycle' AND status = 'completed' AND streak = 0 AND last_completed_at IS NULL",
+        [],
+    )?;
+    Ok(updated)
+}
+
+fn record_cycle_completion(conn: &rusqlite::Connection, task: &Task) -> Result<()> {
+    if task.task_type != "cycle" || task.status != "completed" {
+        return Ok(());
+    }
+    let (streak, last_completed_at): (i64, Option<String>) = conn.query_row(
+        "SELECT streak, last_completed_at FROM tasks WHERE id = ?1",
+        [&task.id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let now = Utc::now();
+    let rule = task.repeat_rule.as_deref();
+    let current = cycle_period(rule, now.with_timezone(&Local).date_naive());
+    let last = last_completed_at
+        .as_deref()
+        .and_then(local_date)
+        .map(|day| cycle_period(rule, day));
+    if last == Some(current) {
+        return Ok(());
+    }
+    let anchor = streak_anchor(
+        rule,
+        last_completed_at.as_deref(),
+        task.frozen_until.as_deref(),
+    );
+    let streak = match anchor {
+        Some(anchor) if anchor >= current - 1 => streak + 1,
+        _ => 1,
+    };
+
+    conn.execute(
+        "UPDATE tasks SET streak = ?1, last_completed_at = ?2 WHERE id = ?3 AND last_completed_at IS ?4",
+        params![streak, now.to_rfc3339(), task.id, last_completed_at],
+    )?;
+    Ok(())
+}
+
+fn import_csv_row(conn: &rusqlite::Connection, fields: &[String]) -> Result<bool> {
+    if fields.len() != crate::export::CSV_COLUMNS.len() {
+        return Err(DbError::InvalidInput(format!(
+            "expected {} fields, found {}",
+            crate::export::CSV_COLUMNS.len(),
+            fields.len()
+        )));
+    }
+    let optional = |index: usize| {
+        let value = fields[index].trim();
+        (!value.is_empty()).then(|| value.to_string())
+    };
+    let target = fields[4]
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| DbError::InvalidInput(format!("target is not a number: {}", fields[4])))?;
+    let date_range = match (optional(6), optional(7)) {
+        (Some(start), Some(end)) => Some(vec![start, end]),
+        _ => None,
+    };
+    let payload = NewTask {
+        id: optional(0),
+        name: fields[1].clone(),
+        description: Some(fields[2].clone()),
+        task_type: fields[3].trim().to_string(),
+        target: Some(target),
+        repeat: optional(5),
+        date_range,
+        color: None,
+        notes: None,
+        regenerate: None,
+        reminder_time: None,
+    };
+    payload.validate()?;
+
+    let (repeat_rule, start_date, end_date) = normalize_schedule(
+        payload.task_type.as_str(),
+        payload.repeat,
+        payload.date_range,
+    )?;
+    let name = normalize_name(&payload.name)?;
+    let description = normalize_description(&payload.description.unwrap_or_default())?;
+    let now = Utc::now().to_rfc3339();
+
+    if let Some(id) = &payload.id {
+        let updated = conn.execute(
+            "UPDATE tasks SET name = ?1, description = ?2, task_type = ?3, target = ?4, repeat_rule = ?5, start_date = ?6, end_date = ?7,
+                progress = MIN(progress, ?4),
+                status = CASE WHEN status = 'archived' THEN status WHEN progress >= ?4 THEN 'completed' ELSE 'active' END,
+                updated_at = ?8
+            WHERE id = ?9",
+            params![
+                name,
+                description,
+                payload.task_type,
+                target,
+                repeat_rule,
+                start_date,
+                end_date,
+                now,
+                id
+            ],
+        )?;
+        if updated > 0 {
+            return Ok(true);
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO tasks (id, name, description, task_type, progress, target, repeat_rule, start_date, end_date, status, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8, 'active', ?9, ?9)",
+        params![
+            payload.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            name,
+            description,
+            payload.task_type,
+            target,
+            repeat_rule,
+            start_date,
+            end_date,
+            now
+        ],
+    )?;
+    Ok(false)
+}
+
+fn changed_fields(old: &Task, new: &Task) -> Result<Vec<String>> {
+    let serde_json::Value::Object(old) = serde_json::to_value(old)? else {
+        return Ok(Vec::new());
+    };
+    let serde_json::Value::Object(new) = serde_json::to_value(new)? else {
+        return Ok(Vec::new());
+    };
+    Ok(new
+        .iter()
+        .filter(|(key, value)| key.as_str() != "progressPercent" && old.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect())
+}
+
+fn like_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+fn fuzzy_score(query: &str, candidate: &str) -> f64 {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if candidate.contains(&query) {
+        return 1.0;
+    }
+
+    let query: Vec<char> = query.chars().collect();
+    std::iter::once(candidate.as_str())
+        .chain(candidate.split_whitespace())
+        .map(|word| {
+            let word: Vec<char> = word.chars().collect();
+            let longest = query.len().max(word.len()).max(1);
+            1.0 - edit_distance(&query, &word) as f64 / longest as f64
         })
+        .fold(0.0, f64::max)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
     }
+    previous[b.len()]
+}
+
+fn progress_percent(progress: i64, target: i64) -> f64 {
+    let ratio = progress as f64 / target.max(1) as f64 * 100.0;
+    (ratio * 10.0).round() / 10.0
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpcomingDeadline {
+    #[serde(flatten)]
+    pub task: Task,
+    pub days_remaining: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionProjection {
+    pub id: String,
+    pub daily_rate: f64,
+    pub projected_completion: Option<String>,
+    pub beats_end_date: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryBucket {
+    pub start: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VelocityTrend {
+    pub weeks: Vec<HistoryBucket>,
+    pub slope: f64,
+    pub trend: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthCount {
+    pub month: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub struct ProgressUpdate {
+    pub task: Task,
+    pub just_completed: bool,
+    pub regenerated: Option<Task>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupedTasks {
+    pub active: Vec<Task>,
+    pub completed: Vec<Task>,
+    pub archived: Vec<Task>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    #[serde(flatten)]
+    pub task: Task,
+    pub depth: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskAnomaly {
+    pub id: String,
+    pub name: String,
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDelta {
+    pub changed: Vec<Task>,
+    pub deleted: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgendaDay {
+    pub date: String,
+    pub weekday: String,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub task: Task,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskTypeCount {
+    #[serde(rename = "type")]
+    pub task_type: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupProgress {
+    pub count: i64,
+    pub progress: i64,
+    pub target: i64,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub name: String,
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyReview {
+    pub week_start: String,
+    pub week_end: String,
+    pub completed: Vec<Task>,
+    pub active: Vec<Task>,
+    pub overdue: Vec<Task>,
+    pub streaks: Vec<StreakEntry>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub committed: bool,
+    pub errors: Vec<CsvRowError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvRowError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDiff {
+    pub added: Vec<Task>,
+    pub removed: Vec<Task>,
+    pub changed: Vec<ChangedTask>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedTask {
+    pub task: Task,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeEntry {
+    pub id: i64,
+    pub task_id: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSpent {
+    pub id: String,
+    pub running: bool,
+    pub total_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressPoint {
+    pub timestamp: String,
+    pub progress: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HabitAge {
+    pub id: String,
+    pub days_tracked: i64,
+    pub streak: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreakEntry {
+    pub id: String,
+    pub name: String,
+    pub streak: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoNotDisturb {
+    pub active: bool,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveTaskWarning {
+    pub active: i64,
+    pub limit: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskExport {
+    pub exported_at: String,
+    pub schema_version: i64,
+    pub filter: TaskQuery,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaVersion {
+    pub schema_version: i64,
+    pub sqlite_version: String,
+    pub app_version: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStats {
+    pub tables: Vec<TableCount>,
+    pub file_size: Option<u64>,
+    pub wal_size: Option<u64>,
+    pub last_vacuum_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableCount {
+    pub name: String,
+    pub rows: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalCheckpoint {
+    pub busy: bool,
+    pub log_frames: i64,
+    pub checkpointed_frames: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactReport {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "type")]
+    pub task_type: String,
+    pub target: i64,
+    pub repeat: Option<String>,
+    pub date_range: Option<Vec<String>>,
+    pub color: Option<String>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -327,6 +3897,32 @@ pub struct NewTask {
     pub target: Option<i64>,
     pub repeat: Option<String>,
     pub date_range: Option<Vec<String>>,
+    pub color: Option<String>,
+    pub notes: Option<String>,
+    pub regenerate: Option<String>,
+    pub reminder_time: Option<String>,
+}
+
+impl NewTask {
+    pub fn validate(&self) -> Result<()> {
+        normalize_name(&self.name)?;
+        if let Some(description) = &self.description {
+            normalize_description(description)?;
+        }
+        validate_task_type(&self.task_type)?;
+        if let Some(target) = self.target {
+            validate_target(target)?;
+        }
+        normalize_schedule(
+            self.task_type.as_str(),
+            self.repeat.clone(),
+            self.date_range.clone(),
+        )?;
+        normalize_color(self.color.clone())?;
+        normalize_regenerate(self.task_type.as_str(), self.regenerate.clone())?;
+        normalize_reminder_time(self.reminder_time.clone())?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -340,6 +3936,156 @@ pub struct UpdateTask {
     pub target: Option<i64>,
     pub repeat: Option<String>,
     pub date_range: Option<Vec<String>>,
+    pub color: Option<String>,
+    pub notes: Option<String>,
+    pub regenerate: Option<String>,
+    pub reminder_time: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskQuery {
+    pub statuses: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    pub task_type: Option<String>,
+    pub created_from: Option<String>,
+    pub created_to: Option<String>,
+    pub updated_from: Option<String>,
+    pub updated_to: Option<String>,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupFilter {
+    #[serde(rename = "type")]
+    pub task_type: Option<String>,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeTasks {
+    pub primary_id: String,
+    pub secondary_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchTasks {
+    pub query: String,
+    pub fuzzy: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchTask {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    pub task_type: Option<String>,
+    pub target: Option<i64>,
+    pub repeat: Option<String>,
+    pub date_range: Option<Vec<String>>,
+    pub color: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdjustTarget {
+    pub id: String,
+    pub delta: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FreezeTask {
+    pub id: String,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<serde_json::Map<String, serde_json::Value>>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyCap {
+    #[serde(rename = "type")]
+    pub task_type: String,
+    pub cap: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReminderTime {
+    pub id: String,
+    pub time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnoozeTask {
+    pub id: String,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameTask {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeTaskType {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub task_type: String,
+    pub repeat: Option<String>,
+    pub date_range: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveTaskCascade {
+    pub id: String,
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriorityChange {
+    pub id: String,
+    pub priority: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetParent {
+    pub id: String,
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveOrphans {
+    pub reparent_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTaskTags {
+    pub id: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -348,4 +4094,923 @@ pub struct IdPayload {
     pub id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteTask {
+    pub id: String,
+    pub token: Option<String>,
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_task(name: &str, task_type: &str, target: i64) -> NewTask {
+        NewTask {
+            id: None,
+            name: name.to_string(),
+            description: None,
+            task_type: task_type.to_string(),
+            target: Some(target),
+            repeat: (task_type == "cycle").then(|| "daily".to_string()),
+            date_range: (task_type == "long_term")
+                .then(|| vec!["2020-01-01".to_string(), "2099-12-31".to_string()]),
+            color: None,
+            notes: None,
+            regenerate: None,
+            reminder_time: None,
+        }
+    }
+
+    fn edit(task: &Task) -> UpdateTask {
+        UpdateTask {
+            id: task.id.clone(),
+            name: task.name.clone(),
+            description: Some(task.description.clone()),
+            task_type: task.task_type.clone(),
+            target: Some(task.target),
+            repeat: task.repeat_rule.clone(),
+            date_range: None,
+            color: task.color.clone(),
+            notes: task.notes.clone(),
+            regenerate: task.regenerate_rule.clone(),
+            reminder_time: None,
+        }
+    }
+
+    #[test]
+    fn in_memory_create_update_increment() {
+        let db = Db::init_in_memory().unwrap();
+        let task = db.create_task(new_task("Read", "once", 2)).unwrap();
+        assert_eq!((task.progress, task.status.as_str()), (0, "active"));
+
+        let mut payload = edit(&task);
+        payload.name = "Read more".to_string();
+        payload.target = Some(3);
+        let task = db.update_task(payload).unwrap();
+        assert_eq!((task.name.as_str(), task.target), ("Read more", 3));
+
+        for _ in 0..2 {
+            db.increment_progress(&task.id).unwrap();
+        }
+        let update = db.increment_progress(&task.id).unwrap();
+        assert!(update.just_completed);
+        assert_eq!(
+            (update.task.progress, update.task.status.as_str()),
+            (3, "completed")
+        );
+        let update = db.increment_progress(&task.id).unwrap();
+        assert!(!update.just_completed);
+        assert_eq!(update.task.progress, 3);
+    }
+
+    #[test]
+    fn targets_are_bounded_on_create_and_update() {
+        let db = Db::init_in_memory().unwrap();
+        for target in [0, -1, -50, MAX_TASK_TARGET + 1] {
+            assert!(matches!(
+                db.create_task(new_task("t", "once", target)),
+                Err(DbError::InvalidInput(_))
+            ));
+        }
+        for target in [1, MAX_TASK_TARGET] {
+            assert_eq!(
+                db.create_task(new_task("t", "once", target))
+                    .unwrap()
+                    .target,
+                target
+            );
+        }
+
+        let task = db.create_task(new_task("u", "once", 5)).unwrap();
+        for target in [0, -1, MAX_TASK_TARGET + 1] {
+            let mut payload = edit(&task);
+            payload.target = Some(target);
+            assert!(matches!(
+                db.update_task(payload),
+                Err(DbError::InvalidInput(_))
+            ));
+        }
+        for target in [1, MAX_TASK_TARGET] {
+            let mut payload = edit(&task);
+            payload.target = Some(target);
+            assert_eq!(db.update_task(payload).unwrap().target, target);
+        }
+    }
+
+    #[test]
+    fn merge_moves_tags_to_primary() {
+        let db = Db::init_in_memory().unwrap();
+        let primary = db.create_task(new_task("Stretch", "once", 4)).unwrap();
+        let secondary = db
+            .create_task(new_task("Stretch again", "once", 4))
+            .unwrap();
+        for (id, tags) in [
+            (&primary.id, vec!["fitness"]),
+            (&secondary.id, vec!["fitness", "morning"]),
+        ] {
+            db.set_task_tags(SetTaskTags {
+                id: id.clone(),
+                tags: tags.into_iter().map(str::to_string).collect(),
+            })
+            .unwrap();
+        }
+
+        let merged = db
+            .merge_tasks(MergeTasks {
+                primary_id: primary.id.clone(),
+                secondary_id: secondary.id.clone(),
+            })
+            .unwrap();
+        assert_eq!(merged.tags, ["fitness", "morning"]);
+        let leftover: i64 = db
+            .conn()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM task_tags WHERE task_id = ?1",
+                [&secondary.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftover, 0);
+    }
+
+    #[test]
+    fn rename_tag_merges_into_existing_tag() {
+        let db = Db::init_in_memory().unwrap();
+        let mut ids = Vec::new();
+        for (name, tags) in [
+            ("Run", vec![" gym ", "gym"]),
+            ("Lift", vec!["gym", "fitness"]),
+            ("Swim", vec!["fitness"]),
+        ] {
+            let task = db.create_task(new_task(name, "once", 1)).unwrap();
+            db.set_task_tags(SetTaskTags {
+                id: task.id.clone(),
+                tags: tags.into_iter().map(str::to_string).collect(),
+            })
+            .unwrap();
+            ids.push(task.id);
+        }
+        let tags_of = |id: &str| db.fetch_task(&db.conn().unwrap(), id).unwrap().tags;
+        assert_eq!(tags_of(&ids[0]), ["gym"]);
+
+        assert!(matches!(
+            db.rename_tag(" ", "fitness"),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            db.rename_tag("gym", "\t"),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert_eq!(db.rename_tag(" gym ", "fitness").unwrap(), 2);
+        for id in &ids {
+            assert_eq!(tags_of(id), ["fitness"]);
+        }
+        assert_eq!(db.rename_tag("gym", "fitness").unwrap(), 0);
+    }
+
+    fn nest(db: &Db, id: &str, parent: Option<&str>) -> Result<Task> {
+        db.set_parent(SetParent {
+            id: id.to_string(),
+            parent_id: parent.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let db = Db::init_in_memory().unwrap();
+        let root = db.create_task(new_task("Root", "once", 1)).unwrap();
+        let child = db.create_task(new_task("Child", "once", 1)).unwrap();
+        let grandchild = db.create_task(new_task("Grandchild", "once", 1)).unwrap();
+        nest(&db, &child.id, Some(&root.id)).unwrap();
+        let nested = nest(&db, &grandchild.id, Some(&child.id)).unwrap();
+        assert_eq!(nested.parent_id.as_deref(), Some(child.id.as_str()));
+
+        assert!(matches!(
+            nest(&db, &root.id, Some(&root.id)),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            nest(&db, &root.id, Some(&grandchild.id)),
+            Err(DbError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            nest(&db, &root.id, Some("missing")),
+            Err(DbError::NotFound(_))
+        ));
+        assert_eq!(nest(&db, &grandchild.id, None).unwrap().parent_id, None);
+    }
+
+    #[test]
+    fn orphans_are_found_and_resolved() {
+        let db = Db::init_in_memory().unwrap();
+        let archived = db.create_task(new_task("Old project", "once", 1)).unwrap();
+        let deleted = db.create_task(new_task("Gone", "once", 1)).unwrap();
+        let home = db.create_task(new_task("Inbox", "once", 1)).unwrap();
+        let first = db.create_task(new_task("First", "once", 1)).unwrap();
+        let second = db.create_task(new_task("Second", "once", 1)).unwrap();
+        nest(&db, &first.id, Some(&archived.id)).unwrap();
+        nest(&db, &second.id, Some(&deleted.id)).unwrap();
+        db.archive_task(&archived.id).unwrap();
+        db.delete_task(&deleted.id).unwrap();
+
+        let ids = |tasks: Vec<Task>| tasks.into_iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(db.find_orphans().unwrap()),
+            [first.id.clone(), second.id.clone()]
+        );
+        let resolved = db
+            .resolve_orphans(ResolveOrphans {
+                reparent_to: Some(home.id.clone()),
+            })
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert!(db.find_orphans().unwrap().is_empty());
+        let parent_of = |id: &str| db.fetch_task(&db.conn().unwrap(), id).unwrap().parent_id;
+        assert_eq!(parent_of(&second.id), Some(home.id.clone()));
+
+        db.archive_task(&home.id).unwrap();
+        db.resolve_orphans(ResolveOrphans { reparent_to: None })
+            .unwrap();
+        assert_eq!(parent_of(&first.id), None);
+    }
+
+    struct TempDb {
+        db: Db,
+        dir: PathBuf,
+    }
+
+    impl TempDb {
+        fn open() -> Self {
+            let dir = std::env::temp_dir().join(format!("timemaster-test-{}", Uuid::new_v4()));
+            let db = Db::open(dir.join("timemaster.db")).unwrap();
+            Self { db, dir }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn concurrent_increments_are_not_lost() {
+        const THREADS: i64 = 8;
+        const INCREMENTS: i64 = 40;
+        let temp = TempDb::open();
+        for target in [THREADS * INCREMENTS + 10, THREADS * INCREMENTS - 25] {
+            let task = temp
+                .db
+                .create_task(new_task("hammer", "once", target))
+                .unwrap();
+            let workers = (0..THREADS)
+                .map(|_| {
+                    let db = temp.db.clone();
+                    let id = task.id.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..INCREMENTS {
+                            db.increment_progress(&id).unwrap();
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            let task = temp
+                .db
+                .fetch_task(&temp.db.conn().unwrap(), &task.id)
+                .unwrap();
+            assert_eq!(task.progress, (THREADS * INCREMENTS).min(target));
+        }
+    }
+
+    #[test]
+    fn archive_cascade_walks_the_whole_subtree() {
+        let db = Db::init_in_memory().unwrap();
+        let root = db.create_task(new_task("Root", "once", 1)).unwrap();
+        let mut parent = root.id.clone();
+        let mut chain = Vec::new();
+        for depth in 0..50 {
+            let task = db
+                .create_task(new_task(&format!("Level {depth}"), "once", 1))
+                .unwrap();
+            nest(&db, &task.id, Some(&parent)).unwrap();
+            parent = task.id.clone();
+            chain.push(task.id);
+        }
+        let sibling = db.create_task(new_task("Sibling", "once", 1)).unwrap();
+        nest(&db, &sibling.id, Some(&root.id)).unwrap();
+        db.archive_task(&chain[10]).unwrap();
+        let outside = db.create_task(new_task("Outside", "once", 1)).unwrap();
+        let status_of = |id: &str| db.fetch_task(&db.conn().unwrap(), id).unwrap().status;
+
+        let parent_only = db
+            .archive_task_cascade(ArchiveTaskCascade {
+                id: chain[0].clone(),
+                cascade: false,
+            })
+            .unwrap();
+        assert_eq!(parent_only.len(), 1);
+        assert_eq!(status_of(&chain[1]), "active");
+
+        let archived = db
+            .archive_task_cascade(ArchiveTaskCascade {
+                id: root.id.clone(),
+                cascade: true,
+            })
+            .unwrap();
+        // Root, the sibling and 48 chain levels: chain[0] and chain[10] were already archived.
+        assert_eq!(archived.len(), 50);
+        assert!(archived.iter().all(|task| task.status == "archived"));
+        assert_eq!(status_of(&chain[49]), "archived");
+        assert_eq!(status_of(&outside.id), "active");
+    }
+
+    #[test]
+    fn name_and_description_lengths_count_chars() {
+        let db = Db::init_in_memory().unwrap();
+        let name = "é".repeat(MAX_NAME_LENGTH);
+        assert_eq!(
+            db.create_task(new_task(&name, "once", 1))
+                .unwrap()
+                .name
+                .chars()
+                .count(),
+            MAX_NAME_LENGTH
+        );
+        let too_long = "é".repeat(MAX_NAME_LENGTH + 1);
+        assert!(matches!(
+            db.create_task(new_task(&too_long, "once", 1)),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        let mut payload = new_task("d", "once", 1);
+        payload.description = Some("日".repeat(MAX_DESCRIPTION_LENGTH));
+        assert_eq!(
+            db.create_task(payload).unwrap().description.chars().count(),
+            MAX_DESCRIPTION_LENGTH
+        );
+        let mut payload = new_task("d", "once", 1);
+        payload.description = Some("日".repeat(MAX_DESCRIPTION_LENGTH + 1));
+        assert!(matches!(
+            db.create_task(payload),
+            Err(DbError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn control_characters_are_stripped() {
+        let db = Db::init_in_memory().unwrap();
+        let mut payload = new_task("Wa\u{7}ter\u{0} plants\u{1b}", "once", 1);
+        payload.description = Some("every\u{8} morning\u{7f}".to_string());
+        let task = db.create_task(payload).unwrap();
+        assert_eq!(task.name, "Water plants");
+        assert_eq!(task.description, "every morning");
+    }
+
+    #[test]
+    fn group_progress_by_tag() {
+        let db = Db::init_in_memory().unwrap();
+        for (name, target) in [("Run", 4), ("Lift", 1)] {
+            let task = db.create_task(new_task(name, "once", target)).unwrap();
+            db.set_task_tags(SetTaskTags {
+                id: task.id.clone(),
+                tags: vec!["fitness".to_string()],
+            })
+            .unwrap();
+            if name == "Run" {
+                db.increment_progress(&task.id).unwrap();
+            }
+        }
+        db.create_task(new_task("Read", "once", 10)).unwrap();
+
+        let group = |task_type: Option<&str>, tag: Option<&str>| {
+            db.group_progress(GroupFilter {
+                task_type: task_type.map(str::to_string),
+                tag: tag.map(str::to_string),
+            })
+        };
+        let fitness = group(None, Some("fitness")).unwrap();
+        assert_eq!((fitness.count, fitness.progress, fitness.target), (2, 1, 5));
+        let empty = group(None, Some("music")).unwrap();
+        assert_eq!((empty.count, empty.ratio), (0, 0.0));
+        assert!(matches!(group(None, None), Err(DbError::InvalidInput(_))));
+        assert!(matches!(
+            group(Some("once"), Some("fitness")),
+            Err(DbError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn export_filters_by_tag_and_records_it() {
+        let temp = TempDb::open();
+        let db = &temp.db;
+        let run = db.create_task(new_task("Run", "once", 1)).unwrap();
+        db.create_task(new_task("Read", "once", 1)).unwrap();
+        db.set_task_tags(SetTaskTags {
+            id: run.id.clone(),
+            tags: vec!["fitness".to_string()],
+        })
+        .unwrap();
+
+        let path = temp.dir.join("fitness.json");
+        let filter = TaskQuery {
+            tag: Some("fitness".to_string()),
+            ..TaskQuery::default()
+        };
+        assert_eq!(db.export_tasks(&path, filter).unwrap(), 1);
+        let export: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(export["filter"]["tag"], "fitness");
+        assert_eq!(export["tasks"].as_array().unwrap().len(), 1);
+        assert_eq!(export["tasks"][0]["tags"], serde_json::json!(["fitness"]));
+    }
+
+    #[test]
+    fn holding_every_connection_exhausts_the_pool() {
+        let temp = TempDb::open();
+        let pool = temp.db.pool();
+        let held = (0..pool.max_size())
+            .map(|_| pool.get().unwrap())
+            .collect::<Vec<_>>();
+        let Err(err) = temp.db.conn() else {
+            panic!("checkout should fail while every connection is held");
+        };
+        assert!(matches!(err, DbError::PoolExhausted(_)), "{err:?}");
+        assert!(matches!(
+            crate::error::ApiError::from(err),
+            crate::error::ApiError::PoolUnavailable(_)
+        ));
+        drop(held);
+        assert!(temp.db.conn().is_ok());
+    }
+
+    #[test]
+    fn list_tree_is_preorder_and_survives_cycles() {
+        let db = Db::init_in_memory().unwrap();
+        let make = |name: &str| db.create_task(new_task(name, "once", 1)).unwrap().id;
+        let (a, a1, a1x, a2, b) = (make("A"), make("A1"), make("A1x"), make("A2"), make("B"));
+        nest(&db, &a1, Some(&a)).unwrap();
+        nest(&db, &a1x, Some(&a1)).unwrap();
+        nest(&db, &a2, Some(&a)).unwrap();
+        let (loop_a, loop_b) = (make("Loop A"), make("Loop B"));
+        {
+            let conn = db.conn().unwrap();
+            for (id, parent) in [(&loop_a, &loop_b), (&loop_b, &loop_a)] {
+                conn.execute(
+                    "UPDATE tasks SET parent_id = ?1 WHERE id = ?2",
+                    [parent, id],
+                )
+                .unwrap();
+            }
+        }
+
+        let flat = |root: Option<&str>| {
+            db.list_tree(root)
+                .unwrap()
+                .into_iter()
+                .map(|node| (node.task.name, node.depth))
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            ("A", 0),
+            ("A1", 1),
+            ("A1x", 2),
+            ("A2", 1),
+            ("B", 0),
+            ("Loop A", 0),
+            ("Loop B", 1),
+        ];
+        assert_eq!(
+            flat(None),
+            expected.map(|(name, depth)| (name.to_string(), depth))
+        );
+        assert_eq!(
+            flat(Some(&a1)),
+            [("A1".to_string(), 0), ("A1x".to_string(), 1)]
+        );
+        assert_eq!(flat(Some(&loop_b)).len(), 2);
+        assert_eq!(flat(Some(&b)).len(), 1);
+    }
+
+    #[test]
+    fn priority_bulk_is_validated_and_atomic() {
+        let db = Db::init_in_memory().unwrap();
+        let a = db.create_task(new_task("A", "once", 1)).unwrap();
+        let b = db.create_task(new_task("B", "once", 1)).unwrap();
+        assert_eq!(a.priority, 0);
+        let change = |id: &str, priority: i64| PriorityChange {
+            id: id.to_string(),
+            priority,
+        };
+        let priority_of = |id: &str| db.fetch_task(&db.conn().unwrap(), id).unwrap().priority;
+
+        for priority in [-1, MAX_PRIORITY + 1] {
+            assert!(matches!(
+                db.set_priority_bulk(vec![change(&a.id, 1), change(&b.id, priority)]),
+                Err(DbError::InvalidInput(_))
+            ));
+        }
+        assert!(matches!(
+            db.set_priority_bulk(vec![change(&a.id, 2), change("missing", 1)]),
+            Err(DbError::NotFound(_))
+        ));
+        assert_eq!(priority_of(&a.id), 0);
+
+        assert_eq!(
+            db.set_priority_bulk(vec![change(&a.id, MAX_PRIORITY), change(&b.id, 0)])
+                .unwrap(),
+            2
+        );
+        assert_eq!(priority_of(&a.id), MAX_PRIORITY);
+        assert_eq!(db.set_priority_bulk(Vec::new()).unwrap(), 0);
+    }
+
+    #[test]
+    fn next_task_ranks_priority_before_deadline() {
+        let db = Db::init_in_memory().unwrap();
+        let long_term = |name: &str, end: &str| {
+            let mut payload = new_task(name, "long_term", 5);
+            payload.date_range = Some(vec!["2020-01-01".to_string(), end.to_string()]);
+            db.create_task(payload).unwrap().id
+        };
+        let soon = long_term("Due soon", "2098-01-01");
+        let later = long_term("Due later", "2099-01-01");
+        let whenever = db.create_task(new_task("Whenever", "cycle", 1)).unwrap().id;
+        let next = || db.next_task().unwrap().map(|task| task.id);
+        assert_eq!(next(), Some(soon.clone()));
+
+        let set = |id: &str, priority: i64| {
+            db.set_priority_bulk(vec![PriorityChange {
+                id: id.to_string(),
+                priority,
+            }])
+            .unwrap();
+        };
+        set(&whenever, 1);
+        assert_eq!(next(), Some(whenever.clone()));
+        set(&later, 3);
+        set(&soon, 3);
+        assert_eq!(next(), Some(soon));
+    }
+
+    #[test]
+    fn concurrent_updates_keep_every_increment() {
+        const INCREMENTERS: i64 = 6;
+        const INCREMENTS: i64 = 30;
+        let temp = TempDb::open();
+        let task = temp
+            .db
+            .create_task(new_task("mixed", "once", 10_000))
+            .unwrap();
+
+        let mut workers = (0..INCREMENTERS)
+            .map(|_| {
+                let db = temp.db.clone();
+                let id = task.id.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..INCREMENTS {
+                        db.increment_progress(&id).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers.extend((0..3).map(|writer| {
+            let db = temp.db.clone();
+            let id = task.id.clone();
+            std::thread::spawn(move || {
+                for round in 0..20 {
+                    let mut payload = edit(&db.get_task(&id).unwrap());
+                    payload.name = format!("mixed {writer}-{round}");
+                    db.update_task(payload).unwrap();
+                }
+            })
+        }));
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let task = temp.db.get_task(&task.id).unwrap();
+        assert_eq!(task.progress, INCREMENTERS * INCREMENTS);
+        assert!(task.name.starts_with("mixed "));
+    }
+
+    #[test]
+    fn update_keeps_color_unless_cleared() {
+        let db = Db::init_in_memory().unwrap();
+        let mut payload = new_task("Paint", "once", 3);
+        payload.color = Some("#12abEF".to_string());
+        let task = db.create_task(payload).unwrap();
+
+        let mut update = edit(&task);
+        update.color = None;
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.color.as_deref(), Some("#12abEF"));
+
+        let mut update = edit(&task);
+        update.color = Some(String::new());
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.color, None);
+    }
+
+    #[test]
+    fn shrinking_target_completes_like_progress() {
+        let db = Db::init_in_memory().unwrap();
+        let task = db.create_task(new_task("Stretch", "cycle", 3)).unwrap();
+        db.increment_progress(&task.id).unwrap();
+
+        let update = db
+            .adjust_target(AdjustTarget {
+                id: task.id.clone(),
+                delta: -5,
+            })
+            .unwrap();
+        assert!(update.just_completed);
+        assert_eq!(
+            (update.task.target, update.task.status.as_str()),
+            (1, "completed")
+        );
+        let streaks = db.top_streaks(5).unwrap();
+        assert_eq!(streaks[0].streak, 1);
+
+        let update = db
+            .adjust_target(AdjustTarget {
+                id: task.id.clone(),
+                delta: 0,
+            })
+            .unwrap();
+        assert!(!update.just_completed);
+        assert_eq!(db.top_streaks(5).unwrap()[0].streak, 1);
+    }
+
+    #[test]
+    fn type_changes_clear_type_specific_columns() {
+        let db = Db::init_in_memory().unwrap();
+        let cycle = db
+            .create_task(new_task("Water plants", "cycle", 1))
+            .unwrap();
+        db.set_reminder_time(SetReminderTime {
+            id: cycle.id.clone(),
+            time: Some("08:30".to_string()),
+        })
+        .unwrap();
+        db.freeze_task(FreezeTask {
+            id: cycle.id.clone(),
+            until: Some("2099-01-01".to_string()),
+        })
+        .unwrap();
+        let task = db
+            .change_task_type(ChangeTaskType {
+                id: cycle.id.clone(),
+                task_type: "once".to_string(),
+                repeat: None,
+                date_range: None,
+            })
+            .unwrap();
+        assert_eq!((task.frozen_until, task.reminder_time), (None, None));
+
+        let mut payload = new_task("Quarterly goal", "long_term", 5);
+        payload.regenerate = Some(REGENERATE_RULES[0].to_string());
+        let long_term = db.create_task(payload).unwrap();
+        assert!(long_term.regenerate_rule.is_some());
+        let task = db
+            .patch_task(PatchTask {
+                id: long_term.id.clone(),
+                name: None,
+                description: None,
+                task_type: Some("cycle".to_string()),
+                target: None,
+                repeat: Some("daily".to_string()),
+                date_range: None,
+                color: None,
+                notes: None,
+            })
+            .unwrap()
+            .task;
+        assert_eq!(task.regenerate_rule, None);
+
+        db.set_reminder_time(SetReminderTime {
+            id: task.id.clone(),
+            time: Some("21:00".to_string()),
+        })
+        .unwrap();
+        let task = db.clear_schedule(&task.id).unwrap();
+        assert_eq!(task.reminder_time, None);
+    }
+
+    #[test]
+    fn reminder_time_is_set_on_create_and_update() {
+        let db = Db::init_in_memory().unwrap();
+        let mut payload = new_task("Meditate", "cycle", 1);
+        payload.reminder_time = Some("7:5".to_string());
+        assert!(matches!(
+            db.create_task(payload),
+            Err(DbError::InvalidInput(_))
+        ));
+
+        let mut payload = new_task("Meditate", "cycle", 1);
+        payload.reminder_time = Some(" 07:05 ".to_string());
+        let task = db.create_task(payload).unwrap();
+        assert_eq!(task.reminder_time.as_deref(), Some("07:05"));
+
+        let task = db.update_task(edit(&task)).unwrap();
+        assert_eq!(task.reminder_time.as_deref(), Some("07:05"));
+        let mut update = edit(&task);
+        update.reminder_time = Some("22:45".to_string());
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.reminder_time.as_deref(), Some("22:45"));
+        let mut update = edit(&task);
+        update.reminder_time = Some(String::new());
+        assert_eq!(db.update_task(update).unwrap().reminder_time, None);
+
+        let mut payload = new_task("Call bank", "once", 1);
+        payload.reminder_time = Some("09:00".to_string());
+        assert_eq!(db.create_task(payload).unwrap().reminder_time, None);
+    }
+
+    #[test]
+    fn patching_target_settles_completion() {
+        let db = Db::init_in_memory().unwrap();
+        let shrink = |id: &str, target: i64| {
+            db.patch_task(PatchTask {
+                id: id.to_string(),
+                name: None,
+                description: None,
+                task_type: None,
+                target: Some(target),
+                repeat: None,
+                date_range: None,
+                color: None,
+                notes: None,
+            })
+            .unwrap()
+        };
+
+        let cycle = db.create_task(new_task("Stretch", "cycle", 3)).unwrap();
+        db.increment_progress(&cycle.id).unwrap();
+        let update = shrink(&cycle.id, 1);
+        assert!(update.just_completed);
+        assert_eq!(
+            (update.task.progress, update.task.status.as_str()),
+            (1, "completed")
+        );
+        assert_eq!(db.top_streaks(5).unwrap()[0].streak, 1);
+        assert!(!shrink(&cycle.id, 1).just_completed);
+
+        let mut payload = new_task("Quarterly goal", "long_term", 5);
+        payload.regenerate = Some(REGENERATE_RULES[0].to_string());
+        let long_term = db.create_task(payload).unwrap();
+        db.increment_progress(&long_term.id).unwrap();
+        let update = shrink(&long_term.id, 1);
+        assert!(update.just_completed);
+        assert!(update.regenerated.is_some());
+        assert_eq!(update.task.regenerate_rule, None);
+    }
+
+    #[test]
+    fn notes_are_kept_unless_cleared() {
+        let db = Db::init_in_memory().unwrap();
+        let mut payload = new_task("Read", "once", 3);
+        payload.notes = Some("chapter 4".to_string());
+        let task = db.create_task(payload).unwrap();
+
+        let mut update = edit(&task);
+        update.notes = None;
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.notes.as_deref(), Some("chapter 4"));
+
+        let mut update = edit(&task);
+        update.notes = Some(String::new());
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.notes, None);
+
+        let patch = |notes: Option<&str>| {
+            db.patch_task(PatchTask {
+                id: task.id.clone(),
+                name: None,
+                description: None,
+                task_type: None,
+                target: None,
+                repeat: None,
+                date_range: None,
+                color: None,
+                notes: notes.map(str::to_string),
+            })
+            .unwrap()
+            .task
+            .notes
+        };
+        assert_eq!(patch(Some("chapter 5")).as_deref(), Some("chapter 5"));
+        assert_eq!(patch(None).as_deref(), Some("chapter 5"));
+        assert_eq!(patch(Some("  ")), None);
+    }
+
+    #[test]
+    fn regenerate_rule_is_kept_unless_cleared_or_retyped() {
+        let db = Db::init_in_memory().unwrap();
+        let mut payload = new_task("Quarterly goal", "long_term", 5);
+        payload.regenerate = Some("monthly".to_string());
+        let task = db.create_task(payload).unwrap();
+        let dated = |task: &Task| UpdateTask {
+            date_range: Some(vec!["2020-01-01".to_string(), "2099-12-31".to_string()]),
+            ..edit(task)
+        };
+
+        let mut update = dated(&task);
+        update.regenerate = None;
+        let task = db.update_task(update).unwrap();
+        assert_eq!(task.regenerate_rule.as_deref(), Some("monthly"));
+
+        let mut update = dated(&task);
+        update.regenerate = Some(String::new());
+        assert_eq!(db.update_task(update).unwrap().regenerate_rule, None);
+
+        let mut update = dated(&task);
+        update.regenerate = None;
+        update.date_range = None;
+        update.task_type = "once".to_string();
+        assert_eq!(db.update_task(update).unwrap().regenerate_rule, None);
+    }
+
+    #[test]
+    fn delete_removes_tags_and_time_entries() {
+        let db = Db::init_in_memory().unwrap();
+        let task = db.create_task(new_task("Read", "once", 3)).unwrap();
+        db.set_task_tags(SetTaskTags {
+            id: task.id.clone(),
+            tags: vec!["books".to_string()],
+        })
+        .unwrap();
+        db.start_timer(&task.id).unwrap();
+
+        db.delete_task(&task.id).unwrap();
+        let leftovers: i64 = db
+            .conn()
+            .unwrap()
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM task_tags WHERE task_id = ?1)
+                    + (SELECT COUNT(*) FROM time_entries WHERE task_id = ?1)",
+                [&task.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftovers, 0);
+        assert!(matches!(db.get_task(&task.id), Err(DbError::NotFound(_))));
+    }
+
+    #[test]
+    fn delta_does_not_report_snoozed_tasks_as_deleted() {
+        let db = Db::init_in_memory().unwrap();
+        let kept = db.create_task(new_task("Read", "once", 3)).unwrap();
+        let snoozed = db.create_task(new_task("Run", "once", 3)).unwrap();
+        let gone = db.create_task(new_task("Swim", "once", 3)).unwrap();
+        let snoozed = db
+            .snooze_task(SnoozeTask {
+                id: snoozed.id.clone(),
+                until: Some("2099-01-01T00:00:00Z".to_string()),
+            })
+            .unwrap();
+        db.delete_task(&gone.id).unwrap();
+
+        let known = [&kept, &snoozed, &gone]
+            .into_iter()
+            .map(|task| (task.id.clone(), task.updated_at.clone()))
+            .collect();
+        let delta = db.delta_tasks(known, false).unwrap();
+        assert!(delta.changed.is_empty());
+        assert_eq!(delta.deleted, [gone.id]);
+    }
+
+    #[test]
+    fn changes_since_include_deletions() {
+        let db = Db::init_in_memory().unwrap();
+        let since = Utc::now().to_rfc3339();
+        let primary = db.create_task(new_task("Read", "once", 3)).unwrap();
+        let secondary = db.create_task(new_task("Reading", "once", 3)).unwrap();
+        let deleted = db.create_task(new_task("Swim", "once", 3)).unwrap();
+        db.merge_tasks(MergeTasks {
+            primary_id: primary.id.clone(),
+            secondary_id: secondary.id.clone(),
+        })
+        .unwrap();
+        db.delete_task(&deleted.id).unwrap();
+
+        let delta = db.list_changed_since(&since).unwrap();
+        assert_eq!(
+            delta
+                .changed
+                .iter()
+                .map(|task| &task.id)
+                .collect::<Vec<_>>(),
+            [&primary.id]
+        );
+        assert_eq!(delta.deleted, [secondary.id, deleted.id]);
+    }
+}
+
 
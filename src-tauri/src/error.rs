@@ -0,0 +1,44 @@
+﻿use serde::Serialize;
+
+use crate::db::DbError;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum ApiError {
+    NotFound(String),
+    InvalidInput(String),
+    Storage(String),
+    PoolUnavailable(String),
+    Database(String),
+    Window(String),
+    PolicyViolation(String),
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        let message = err.to_string();
+        match err {
+            DbError::NotFound(_)
+            | DbError::InvalidInput(_)
+            | DbError::Json(_)
+            | DbError::PolicyViolation(_) => {
+                log::debug!("rejected request: {message}")
+            }
+            _ => log::error!("database error: {message}"),
+        }
+        match err {
+            DbError::NotFound(_) => ApiError::NotFound(message),
+            DbError::InvalidInput(_) | DbError::Json(_) => ApiError::InvalidInput(message),
+            DbError::AppDir(_) | DbError::Io(_) | DbError::Pool(_) => ApiError::Storage(message),
+            DbError::PoolExhausted(_) => ApiError::PoolUnavailable(message),
+            DbError::Sqlite(_) => ApiError::Database(message),
+            DbError::PolicyViolation(_) => ApiError::PolicyViolation(message),
+        }
+    }
+}
+
+impl From<tauri::Error> for ApiError {
+    fn from(err: tauri::Error) -> Self {
+        ApiError::Window(err.to_string())
+    }
+}
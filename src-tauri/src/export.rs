@@ -0,0 +1,292 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::db::{DbError, HistoryBucket, Result, Task, TaskTypeCount, WeeklyReview};
+
+pub const CSV_COLUMNS: [&str; 8] = [
+    "id",
+    "name",
+    "description",
+    "type",
+    "target",
+    "repeat",
+    "startDate",
+    "endDate",
+];
+
+pub fn write_ics(path: &Path, tasks: &[Task]) -> Result<usize> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut written = 0;
+
+    write_ics_line(&mut out, "BEGIN:VCALENDAR")?;
+    write_ics_line(&mut out, "VERSION:2.0")?;
+    write_ics_line(&mut out, "PRODID:-//TimeMaster//TimeMaster//EN")?;
+    write_ics_line(&mut out, "CALSCALE:GREGORIAN")?;
+
+    for task in tasks {
+        let (Some(start), Some(end)) = (ics_date(&task.start_date), ics_date(&task.end_date))
+        else {
+            continue;
+        };
+        // All-day DTEND is exclusive, so the event has to end the day after end_date.
+        let end = end + Duration::days(1);
+
+        write_ics_line(&mut out, "BEGIN:VEVENT")?;
+        write_ics_line(&mut out, &format!("UID:{}@timemaster", task.id))?;
+        write_ics_line(&mut out, &format!("DTSTAMP:{stamp}"))?;
+        write_ics_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", start.format("%Y%m%d")),
+        )?;
+        write_ics_line(
+            &mut out,
+            &format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")),
+        )?;
+        write_ics_line(
+            &mut out,
+            &format!("SUMMARY:{}", escape_ics_text(&task.name)),
+        )?;
+        if !task.description.is_empty() {
+            write_ics_line(
+                &mut out,
+                &format!("DESCRIPTION:{}", escape_ics_text(&task.description)),
+            )?;
+        }
+        write_ics_line(&mut out, "END:VEVENT")?;
+        written += 1;
+    }
+
+    write_ics_line(&mut out, "END:VCALENDAR")?;
+    out.flush()?;
+    Ok(written)
+}
+
+pub fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut out, value)?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn write_ndjson(path: &Path, tasks: impl Iterator<Item = Result<Task>>) -> Result<usize> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+
+    for task in tasks {
+        serde_json::to_writer(&mut out, &task?)?;
+        out.write_all(b"\n")?;
+        written += 1;
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+pub fn read_csv(path: &Path) -> Result<Vec<(usize, Vec<String>)>> {
+    let content = std::fs::read_to_string(path)?;
+    let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if quoted {
+            match ch {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                '\n' => {
+                    line += 1;
+                    field.push(ch);
+                }
+                other => field.push(other),
+            }
+            continue;
+        }
+        match ch {
+            '"' if field.is_empty() => quoted = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            other => field.push(other),
+        }
+    }
+
+    if quoted {
+        return Err(DbError::InvalidInput(format!(
+            "unterminated quoted field starting on line {record_line}"
+        )));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+const REPORT_STYLE: &str = "body{font-family:-apple-system,'Segoe UI',sans-serif;margin:32px;color:#1f2933}\
+h1{font-size:24px}h2{font-size:18px;margin-top:28px;border-bottom:1px solid #e4e7eb;padding-bottom:4px}\
+table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:6px 8px;border-bottom:1px solid #f0f2f5}\
+th{color:#616e7c;font-weight:600}.muted{color:#9aa5b1}";
+
+pub fn write_html_report(
+    path: &Path,
+    review: &WeeklyReview,
+    types: &[TaskTypeCount],
+    history: &[HistoryBucket],
+) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(
+        out,
+        "<html><head><meta charset=\"utf-8\"><title>TimeMaster report</title>"
+    )?;
+    writeln!(out, "<style>{REPORT_STYLE}</style></head><body>")?;
+    writeln!(out, "<h1>TimeMaster report</h1>")?;
+    writeln!(
+        out,
+        "<p class=\"muted\">Week {} to {}, generated {}</p>",
+        escape_html(&review.week_start),
+        escape_html(&review.week_end),
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    )?;
+
+    writeln!(
+        out,
+        "<h2>Tasks by type</h2><table><tr><th>Type</th><th>Count</th></tr>"
+    )?;
+    for entry in types {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&entry.task_type),
+            entry.count
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(
+        out,
+        "<h2>Completions</h2><table><tr><th>Week of</th><th>Completed</th></tr>"
+    )?;
+    for bucket in history {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&bucket.start),
+            bucket.count
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    write_report_tasks(&mut out, "Completed this week", &review.completed)?;
+    write_report_tasks(&mut out, "Active", &review.active)?;
+    write_report_tasks(&mut out, "Overdue", &review.overdue)?;
+
+    writeln!(
+        out,
+        "<h2>Streaks</h2><table><tr><th>Habit</th><th>Streak</th></tr>"
+    )?;
+    for entry in &review.streaks {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&entry.name),
+            entry.streak
+        )?;
+    }
+    writeln!(out, "</table></body></html>")?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_report_tasks(out: &mut impl Write, title: &str, tasks: &[Task]) -> Result<()> {
+    writeln!(out, "<h2>{}</h2>", escape_html(title))?;
+    if tasks.is_empty() {
+        writeln!(out, "<p class=\"muted\">None</p>")?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "<table><tr><th>Task</th><th>Type</th><th>Progress</th></tr>"
+    )?;
+    for task in tasks {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}/{}</td></tr>",
+            escape_html(&task.name),
+            escape_html(&task.task_type),
+            task.progress,
+            task.target
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    Ok(())
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+fn ics_date(value: &Option<String>) -> Option<NaiveDate> {
+    value
+        .as_deref()
+        .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+}
+
+fn escape_ics_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+fn write_ics_line(out: &mut impl Write, line: &str) -> Result<()> {
+    let mut width = 0;
+    for ch in line.chars() {
+        if width + ch.len_utf8() > 75 {
+            out.write_all(b"\r\n ")?;
+            width = 1;
+        }
+        let mut buf = [0u8; 4];
+        out.write_all(ch.encode_utf8(&mut buf).as_bytes())?;
+        width += ch.len_utf8();
+    }
+    out.write_all(b"\r\n")?;
+    Ok(())
+}
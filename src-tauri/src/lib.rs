@@ -1,53 +1,769 @@
 ﻿mod db;
+mod error;
+mod export;
+mod logging;
 
-use crate::db::{Db, IdPayload, NewTask, Task, UpdateTask};
-use serde::Deserialize;
-use std::sync::{Arc, Mutex};
+use crate::db::{
+    AdjustTarget, AgendaDay, ArchiveTaskCascade, ChangeTaskType, CompactReport,
+    CompletionProjection, CsvImportReport, DailyCap, Db, DbError, DbStats, DeleteTask,
+    DoNotDisturb, DuplicateGroup, ExportDiff, FreezeTask, GroupFilter, GroupProgress, GroupedTasks,
+    HabitAge, HistoryBucket, IdPayload, MergeTasks, MonthCount, NewTask, PatchTask, PriorityChange,
+    ProgressPoint, ProgressUpdate, QueryResult, RenameTask, ResolveOrphans, SchemaVersion,
+    SearchHit, SearchTasks, SetParent, SetReminderTime, SetTaskTags, SnoozeTask, StreakEntry, Task,
+    TaskAnomaly, TaskDelta, TaskQuery, TaskTypeCount, TimeEntry, TimeSpent, TreeNode,
+    UpcomingDeadline, UpdateTask, VelocityTrend, WalCheckpoint, WeeklyReview, WindowGeometry,
+    DEFAULT_TASKBAR_OFFSET,
+};
+use crate::error::ApiError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, State, WebviewWindow};
-use tauri_plugin_global_shortcut::GlobalShortcutExt;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{
+    AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, Monitor, PhysicalPosition,
+    PhysicalSize, State, WebviewWindow, Window, WindowEvent,
+};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum TaskChangeKind {
+    Created,
+    Updated,
+    Deleted,
+    Progressed,
+    Archived,
+    Reopened,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct TaskChanged {
+    id: Option<String>,
+    kind: TaskChangeKind,
+}
+
+fn emit_task_changed(app: &AppHandle, id: Option<&str>, kind: TaskChangeKind) {
+    let payload = TaskChanged {
+        id: id.map(str::to_string),
+        kind,
+    };
+    let _ = app.emit("time-master::task-changed", payload);
+    refresh_tray_tooltip(app);
+}
+
+fn emit_progress(app: &AppHandle, update: &ProgressUpdate) {
+    emit_task_changed(app, Some(&update.task.id), TaskChangeKind::Progressed);
+    if update.just_completed {
+        let _ = app.emit("time-master::task-completed", &update.task);
+    }
+    if let Some(task) = &update.regenerated {
+        emit_task_changed(app, Some(&task.id), TaskChangeKind::Created);
+    }
+}
+
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+fn list_tasks(
+    db: State<Db>,
+    statuses: Option<Vec<String>>,
+    include_snoozed: Option<bool>,
+) -> Result<Vec<Task>, ApiError> {
+    db.list_tasks(statuses, include_snoozed.unwrap_or(false))
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn delta_tasks(
+    db: State<Db>,
+    known: HashMap<String, String>,
+    include_snoozed: Option<bool>,
+) -> Result<TaskDelta, ApiError> {
+    db.delta_tasks(known, include_snoozed.unwrap_or(false))
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn list_grouped(db: State<Db>) -> Result<GroupedTasks, ApiError> {
+    db.list_grouped().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn search_tasks(db: State<Db>, payload: SearchTasks) -> Result<Vec<SearchHit>, ApiError> {
+    db.search_tasks(payload).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn list_tasks_in_range(
+    db: State<Db>,
+    from: String,
+    to: String,
+    field: String,
+) -> Result<Vec<Task>, ApiError> {
+    db.list_tasks_in_range(&from, &to, &field)
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn completed_on(db: State<Db>, date: String) -> Result<Vec<Task>, ApiError> {
+    db.completed_on(&date).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn week_agenda(db: State<Db>) -> Result<Vec<AgendaDay>, ApiError> {
+    db.week_agenda().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn list_changed_since(db: State<Db>, since: String) -> Result<TaskDelta, ApiError> {
+    db.list_changed_since(&since).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn query_tasks(db: State<Db>, query: TaskQuery) -> Result<Vec<Task>, ApiError> {
+    db.query_tasks(query).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn get_today(
+    db: State<Db>,
+    include_completed: Option<bool>,
+    include_snoozed: Option<bool>,
+) -> Result<Vec<Task>, ApiError> {
+    db.get_today(
+        include_completed.unwrap_or(false),
+        include_snoozed.unwrap_or(false),
+    )
+    .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn next_task(db: State<Db>) -> Result<Option<Task>, ApiError> {
+    db.next_task().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn validate_task(payload: NewTask) -> Result<(), ApiError> {
+    payload.validate().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn create_tasks(
+    app: AppHandle,
+    db: State<Db>,
+    payloads: Vec<NewTask>,
+) -> Result<Vec<Task>, ApiError> {
+    let tasks = db.create_tasks(payloads)?;
+    if !tasks.is_empty() {
+        emit_task_changed(&app, None, TaskChangeKind::Created);
+    }
+    Ok(tasks)
+}
+
+#[tauri::command]
+fn create_task(app: AppHandle, db: State<Db>, payload: NewTask) -> Result<Task, ApiError> {
+    let task = db.create_task(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Created);
+    match db.active_task_warning() {
+        Ok(Some(warning)) => {
+            let _ = app.emit("time-master::active-task-limit", warning);
+        }
+        Ok(None) => {}
+        Err(err) => log::warn!("failed to check active task limit: {err}"),
+    }
+    Ok(task)
+}
+
+#[tauri::command]
+fn update_task(app: AppHandle, db: State<Db>, payload: UpdateTask) -> Result<Task, ApiError> {
+    let task = db.update_task(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn patch_task(app: AppHandle, db: State<Db>, payload: PatchTask) -> Result<Task, ApiError> {
+    let update = db.patch_task(payload)?;
+    if update.just_completed {
+        emit_progress(&app, &update);
+    } else {
+        emit_task_changed(&app, Some(&update.task.id), TaskChangeKind::Updated);
+    }
+    Ok(update.task)
+}
+
+#[tauri::command]
+fn rename_task(app: AppHandle, db: State<Db>, payload: RenameTask) -> Result<Task, ApiError> {
+    let task = db.rename_task(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn set_task_tags(app: AppHandle, db: State<Db>, payload: SetTaskTags) -> Result<Task, ApiError> {
+    let task = db.set_task_tags(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn change_task_type(
+    app: AppHandle,
+    db: State<Db>,
+    payload: ChangeTaskType,
+) -> Result<Task, ApiError> {
+    let task = db.change_task_type(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn clear_schedule(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let task = db.clear_schedule(&payload.id)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn set_parent(app: AppHandle, db: State<Db>, payload: SetParent) -> Result<Task, ApiError> {
+    let task = db.set_parent(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn list_tree(db: State<Db>, root: Option<String>) -> Result<Vec<TreeNode>, ApiError> {
+    db.list_tree(root.as_deref()).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn find_orphans(db: State<Db>) -> Result<Vec<Task>, ApiError> {
+    db.find_orphans().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn resolve_orphans(
+    app: AppHandle,
+    db: State<Db>,
+    payload: ResolveOrphans,
+) -> Result<Vec<Task>, ApiError> {
+    let orphans = db.resolve_orphans(payload)?;
+    if !orphans.is_empty() {
+        emit_task_changed(&app, None, TaskChangeKind::Updated);
+    }
+    Ok(orphans)
+}
+
+#[tauri::command]
+fn set_priority_bulk(
+    app: AppHandle,
+    db: State<Db>,
+    payload: Vec<PriorityChange>,
+) -> Result<usize, ApiError> {
+    let updated = db.set_priority_bulk(payload)?;
+    if updated > 0 {
+        emit_task_changed(&app, None, TaskChangeKind::Updated);
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+fn rename_tag(app: AppHandle, db: State<Db>, from: String, to: String) -> Result<usize, ApiError> {
+    let affected = db.rename_tag(&from, &to)?;
+    if affected > 0 {
+        emit_task_changed(&app, None, TaskChangeKind::Updated);
+    }
+    Ok(affected)
+}
+
+#[tauri::command]
+fn merge_tasks(app: AppHandle, db: State<Db>, payload: MergeTasks) -> Result<Task, ApiError> {
+    let secondary_id = payload.secondary_id.clone();
+    let task = db.merge_tasks(payload)?;
+    emit_task_changed(&app, Some(&secondary_id), TaskChangeKind::Deleted);
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn request_delete(
+    db: State<Db>,
+    guard: State<DeleteGuard>,
+    payload: IdPayload,
+) -> Result<String, ApiError> {
+    db.get_task(&payload.id)?;
+    Ok(guard.issue(&payload.id))
+}
+
+#[tauri::command]
+fn delete_task(
+    app: AppHandle,
+    db: State<Db>,
+    guard: State<DeleteGuard>,
+    payload: DeleteTask,
+) -> Result<(), ApiError> {
+    let confirmed = payload.force
+        || payload
+            .token
+            .as_deref()
+            .is_some_and(|token| guard.redeem(token, &payload.id));
+    // Without a token or force the task is only archived, so it can still be
+    // reopened; removing it for good needs the explicit confirmation.
+    if !confirmed {
+        let task = db.archive_task(&payload.id)?;
+        emit_task_changed(&app, Some(&task.id), TaskChangeKind::Archived);
+        return Ok(());
+    }
+    db.delete_task(&payload.id)?;
+    emit_task_changed(&app, Some(&payload.id), TaskChangeKind::Deleted);
+    Ok(())
+}
+
+#[tauri::command]
+fn increase_task_progress(
+    app: AppHandle,
+    db: State<Db>,
+    payload: IdPayload,
+) -> Result<Task, ApiError> {
+    let update = db.increment_progress(&payload.id)?;
+    emit_progress(&app, &update);
+    Ok(update.task)
+}
+
+#[tauri::command]
+fn adjust_target(app: AppHandle, db: State<Db>, payload: AdjustTarget) -> Result<Task, ApiError> {
+    let update = db.adjust_target(payload)?;
+    emit_progress(&app, &update);
+    Ok(update.task)
+}
+
+#[tauri::command]
+fn complete_task(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let update = db.complete_task(&payload.id)?;
+    emit_progress(&app, &update);
+    Ok(update.task)
+}
+
+#[tauri::command]
+fn toggle_complete(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let update = db.toggle_complete(&payload.id)?;
+    if update.task.status == "completed" {
+        emit_progress(&app, &update);
+    } else {
+        emit_task_changed(&app, Some(&update.task.id), TaskChangeKind::Reopened);
+    }
+    Ok(update.task)
+}
+
+#[tauri::command]
+fn snooze_task(app: AppHandle, db: State<Db>, payload: SnoozeTask) -> Result<Task, ApiError> {
+    let task = db.snooze_task(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn run_query(db: State<Db>, sql: String) -> Result<QueryResult, ApiError> {
+    db.run_query(&sql).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn daily_caps(db: State<Db>) -> Result<Vec<DailyCap>, ApiError> {
+    db.daily_caps().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn set_daily_cap(db: State<Db>, payload: DailyCap) -> Result<DailyCap, ApiError> {
+    db.set_daily_cap(payload).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn set_reminder_time(
+    app: AppHandle,
+    db: State<Db>,
+    payload: SetReminderTime,
+) -> Result<Task, ApiError> {
+    let task = db.set_reminder_time(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn start_timer(db: State<Db>, payload: IdPayload) -> Result<TimeEntry, ApiError> {
+    db.start_timer(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn stop_timer(db: State<Db>, payload: IdPayload) -> Result<Option<TimeEntry>, ApiError> {
+    db.stop_timer(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn total_time_spent(db: State<Db>, payload: IdPayload) -> Result<TimeSpent, ApiError> {
+    db.total_time_spent(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn freeze_task(app: AppHandle, db: State<Db>, payload: FreezeTask) -> Result<Task, ApiError> {
+    let task = db.freeze_task(payload)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Updated);
+    Ok(task)
+}
+
+#[tauri::command]
+fn archive_task(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let task = db.archive_task(&payload.id)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Archived);
+    Ok(task)
+}
+
+#[tauri::command]
+fn archive_task_cascade(
+    app: AppHandle,
+    db: State<Db>,
+    payload: ArchiveTaskCascade,
+) -> Result<Vec<Task>, ApiError> {
+    let archived = db.archive_task_cascade(payload)?;
+    if !archived.is_empty() {
+        emit_task_changed(&app, None, TaskChangeKind::Archived);
+    }
+    Ok(archived)
+}
+
+#[tauri::command]
+fn archive_matching(app: AppHandle, db: State<Db>, query: String) -> Result<Vec<String>, ApiError> {
+    let ids = db.archive_matching(&query)?;
+    if !ids.is_empty() {
+        emit_task_changed(&app, None, TaskChangeKind::Archived);
+    }
+    Ok(ids)
+}
+
+#[tauri::command]
+fn list_stale(db: State<Db>, days: i64) -> Result<Vec<Task>, ApiError> {
+    db.list_stale(days).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn auto_archive_completed(
+    app: AppHandle,
+    db: State<Db>,
+    older_than_days: i64,
+) -> Result<usize, ApiError> {
+    let archived = db.auto_archive_completed(older_than_days)?;
+    if archived > 0 {
+        emit_task_changed(&app, None, TaskChangeKind::Archived);
+    }
+    Ok(archived)
+}
+
+#[tauri::command]
+fn reopen_task(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let task = db.reopen_task(&payload.id)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Reopened);
+    Ok(task)
+}
+
+#[tauri::command]
+fn resetting_today(db: State<Db>) -> Result<Vec<Task>, ApiError> {
+    db.resetting_today().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn reset_cycle(app: AppHandle, db: State<Db>, payload: IdPayload) -> Result<Task, ApiError> {
+    let task = db.reset_cycle(&payload.id)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Progressed);
+    Ok(task)
+}
+
+#[tauri::command]
+fn find_invalid_ranges(db: State<Db>) -> Result<Vec<Task>, ApiError> {
+    db.find_invalid_ranges().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn upcoming_deadlines(db: State<Db>, within_days: i64) -> Result<Vec<UpcomingDeadline>, ApiError> {
+    db.upcoming_deadlines(within_days).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn progress_timeline(db: State<Db>, payload: IdPayload) -> Result<Vec<ProgressPoint>, ApiError> {
+    db.progress_timeline(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn habit_age(db: State<Db>, payload: IdPayload) -> Result<HabitAge, ApiError> {
+    db.habit_age(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn project_completion(db: State<Db>, payload: IdPayload) -> Result<CompletionProjection, ApiError> {
+    db.project_completion(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn completion_history(
+    db: State<Db>,
+    bucket: String,
+    periods: i64,
+) -> Result<Vec<HistoryBucket>, ApiError> {
+    db.completion_history(&bucket, periods)
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn velocity_trend(db: State<Db>, weeks: i64) -> Result<VelocityTrend, ApiError> {
+    db.velocity_trend(weeks).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn creation_histogram(db: State<Db>, months: i64) -> Result<Vec<MonthCount>, ApiError> {
+    db.creation_histogram(months).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn list_task_types(db: State<Db>) -> Result<Vec<TaskTypeCount>, ApiError> {
+    db.list_task_types().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn group_progress(db: State<Db>, filter: GroupFilter) -> Result<GroupProgress, ApiError> {
+    db.group_progress(filter).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn find_duplicates(db: State<Db>) -> Result<Vec<DuplicateGroup>, ApiError> {
+    db.find_duplicates().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn weekly_review(db: State<Db>) -> Result<WeeklyReview, ApiError> {
+    db.weekly_review().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn top_streaks(db: State<Db>, limit: i64) -> Result<Vec<StreakEntry>, ApiError> {
+    db.top_streaks(limit).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn export_report(db: State<Db>, path: String) -> Result<String, ApiError> {
+    db.export_report(Path::new(&path))?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn export_ics(db: State<Db>, path: String) -> Result<usize, ApiError> {
+    db.export_ics(Path::new(&path)).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn export_tasks(db: State<Db>, path: String, filter: Option<TaskQuery>) -> Result<usize, ApiError> {
+    db.export_tasks(Path::new(&path), filter.unwrap_or_default())
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn diff_against_export(db: State<Db>, path: String) -> Result<ExportDiff, ApiError> {
+    db.diff_against_export(Path::new(&path))
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn export_tasks_ndjson(db: State<Db>, path: String) -> Result<usize, ApiError> {
+    db.export_tasks_ndjson(Path::new(&path))
+        .map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn export_task(db: State<Db>, payload: IdPayload) -> Result<String, ApiError> {
+    db.export_task(&payload.id).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn import_task(app: AppHandle, db: State<Db>, json: String) -> Result<Task, ApiError> {
+    let task = db.import_task(&json)?;
+    emit_task_changed(&app, Some(&task.id), TaskChangeKind::Created);
+    Ok(task)
+}
+
+#[tauri::command]
+fn import_tasks_csv(
+    app: AppHandle,
+    db: State<Db>,
+    path: String,
+    partial: Option<bool>,
+) -> Result<CsvImportReport, ApiError> {
+    let report = db.import_tasks_csv(Path::new(&path), partial.unwrap_or(false))?;
+    if report.committed && report.created + report.updated > 0 {
+        emit_task_changed(&app, None, TaskChangeKind::Updated);
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+fn get_schema_version(db: State<Db>) -> Result<SchemaVersion, ApiError> {
+    db.schema_version().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn reconcile_imported(app: AppHandle, db: State<Db>) -> Result<Vec<TaskAnomaly>, ApiError> {
+    let anomalies = db.reconcile_imported()?;
+    if !anomalies.is_empty() {
+        emit_task_changed(&app, None, TaskChangeKind::Updated);
+    }
+    Ok(anomalies)
+}
+
+#[tauri::command]
+fn backfill_streaks(db: State<Db>) -> Result<usize, ApiError> {
+    db.backfill_streaks().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn set_data_dir(app: AppHandle, db: State<Db>, dir: Option<String>) -> Result<(), ApiError> {
+    let path = db.relocate(&app, dir.as_deref())?;
+    let _ = app.emit(
+        "time-master::data-dir-changed",
+        path.to_string_lossy().to_string(),
+    );
+    app.restart()
+}
+
+#[tauri::command]
+fn get_profile(app: AppHandle) -> Result<String, ApiError> {
+    Db::active_profile(&app).map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn switch_profile(app: AppHandle, db: State<Db>, name: String) -> Result<(), ApiError> {
+    db.switch_profile(&app, &name)?;
+    let _ = app.emit("time-master::profile-changed", name.trim());
+    emit_task_changed(&app, None, TaskChangeKind::Updated);
+    Ok(())
+}
 
 #[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+fn compact_database(db: State<Db>) -> Result<CompactReport, ApiError> {
+    db.compact_database().map_err(ApiError::from)
+}
+
+#[tauri::command]
+fn db_stats(db: State<Db>) -> Result<DbStats, ApiError> {
+    db.db_stats().map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn list_tasks(db: State<Db>, status: Option<String>) -> Result<Vec<Task>, String> {
-    db.list_tasks(status).map_err(|e| e.to_string())
+fn checkpoint_wal(db: State<Db>) -> Result<WalCheckpoint, ApiError> {
+    let checkpoint = db.checkpoint_wal()?;
+    log_wal_checkpoint(&checkpoint);
+    Ok(checkpoint)
+}
+
+const WAL_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+fn log_wal_checkpoint(checkpoint: &WalCheckpoint) {
+    log::debug!(
+        "wal checkpoint: busy={} log_frames={} checkpointed_frames={}",
+        checkpoint.busy,
+        checkpoint.log_frames,
+        checkpoint.checkpointed_frames
+    );
+}
+
+const REMINDER_POLL_INTERVAL: Duration = Duration::from_secs(20);
+
+fn spawn_reminders(app: &AppHandle) {
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let mut last_minute = String::new();
+        loop {
+            let minute = chrono::Local::now().format("%H:%M").to_string();
+            if minute != last_minute {
+                if let Some(db) = handle.try_state::<Db>() {
+                    let due = match db.do_not_disturb() {
+                        Ok(dnd) if dnd.active => Ok(Vec::new()),
+                        Ok(_) => db.due_reminders(&minute),
+                        Err(err) => Err(err),
+                    };
+                    match due {
+                        Ok(tasks) => {
+                            for task in tasks {
+                                let _ = handle.emit("time-master::reminder", task);
+                            }
+                        }
+                        Err(err) => log::error!("failed to load due reminders: {err}"),
+                    }
+                }
+                last_minute = minute;
+            }
+            std::thread::sleep(REMINDER_POLL_INTERVAL);
+        }
+    });
+}
+
+fn spawn_wal_checkpoints(app: &AppHandle) {
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        if let Some(db) = handle.try_state::<Db>() {
+            match db.checkpoint_wal() {
+                Ok(checkpoint) => log_wal_checkpoint(&checkpoint),
+                Err(err) => log::error!("failed to checkpoint wal: {err}"),
+            }
+        }
+        std::thread::sleep(WAL_CHECKPOINT_INTERVAL);
+    });
 }
 
 #[tauri::command]
-fn create_task(db: State<Db>, payload: NewTask) -> Result<Task, String> {
-    db.create_task(payload).map_err(|e| e.to_string())
+fn get_dnd(db: State<Db>) -> Result<DoNotDisturb, ApiError> {
+    db.do_not_disturb().map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn update_task(db: State<Db>, payload: UpdateTask) -> Result<Task, String> {
-    db.update_task(payload).map_err(|e| e.to_string())
+fn set_dnd(db: State<Db>, until: Option<String>) -> Result<DoNotDisturb, ApiError> {
+    db.set_do_not_disturb(until.as_deref())
+        .map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn delete_task(db: State<Db>, payload: IdPayload) -> Result<(), String> {
-    db.delete_task(&payload.id).map_err(|e| e.to_string())
+fn clear_dnd(db: State<Db>) -> Result<(), ApiError> {
+    db.clear_do_not_disturb().map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn increase_task_progress(db: State<Db>, payload: IdPayload) -> Result<Task, String> {
-    db.increment_progress(&payload.id).map_err(|e| e.to_string())
+fn get_setting(db: State<Db>, key: String) -> Result<Option<String>, ApiError> {
+    db.get_setting(&key).map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn archive_task(db: State<Db>, payload: IdPayload) -> Result<Task, String> {
-    db.archive_task(&payload.id).map_err(|e| e.to_string())
+fn set_setting(db: State<Db>, key: String, value: String) -> Result<(), ApiError> {
+    db.set_setting(&key, &value).map_err(ApiError::from)
 }
 
 #[tauri::command]
-fn reopen_task(db: State<Db>, payload: IdPayload) -> Result<Task, String> {
-    db.reopen_task(&payload.id).map_err(|e| e.to_string())
+fn set_log_level(db: State<Db>, level: String) -> Result<(), ApiError> {
+    let level = level.trim().to_lowercase();
+    if logging::parse_level(&level).is_none() {
+        return Err(ApiError::InvalidInput(format!(
+            "log level must be off, error, warn, info, debug or trace, got {level}"
+        )));
+    }
+    db.set_log_level(&level)?;
+    logging::apply_setting(Some(&level));
+    Ok(())
 }
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Deserialize, Clone, Copy, PartialEq)]
 #[serde(rename_all = "lowercase")]
 enum DockSide {
     Left,
@@ -61,40 +777,338 @@ impl DockSide {
             DockSide::Right => "right",
         }
     }
+
+    fn from_setting(value: &str) -> Option<Self> {
+        match value {
+            "left" => Some(DockSide::Left),
+            "right" => Some(DockSide::Right),
+            _ => None,
+        }
+    }
+}
+
+fn stored_dock_side(app: &AppHandle) -> DockSide {
+    app.try_state::<Db>()
+        .and_then(|db| db.dock_side().ok().flatten())
+        .and_then(|side| DockSide::from_setting(&side))
+        .unwrap_or(DockSide::Right)
+}
+
+#[derive(Clone, Copy)]
+enum PanelMode {
+    Compact,
+    Full,
+}
+
+impl PanelMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PanelMode::Compact => "compact",
+            PanelMode::Full => "full",
+        }
+    }
+
+    fn from_setting(value: &str) -> Option<Self> {
+        match value {
+            "compact" => Some(PanelMode::Compact),
+            "full" => Some(PanelMode::Full),
+            _ => None,
+        }
+    }
+}
+
+fn stored_panel_mode(app: &AppHandle) -> PanelMode {
+    app.try_state::<Db>()
+        .and_then(|db| db.panel_mode().ok().flatten())
+        .and_then(|mode| PanelMode::from_setting(&mode))
+        .unwrap_or(PanelMode::Full)
+}
+
+const PANEL_DEBOUNCE_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Default)]
+struct PanelDebounce {
+    last: Mutex<HashMap<&'static str, Instant>>,
+}
+
+impl PanelDebounce {
+    fn ready(&self, action: &'static str) -> bool {
+        let mut last = self.last.lock().unwrap();
+        let now = Instant::now();
+        if let Some(previous) = last.get(action) {
+            if now.duration_since(*previous) < PANEL_DEBOUNCE_INTERVAL {
+                return false;
+            }
+        }
+        last.insert(action, now);
+        true
+    }
+}
+
+const DELETE_TOKEN_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct DeleteGuard {
+    tokens: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl DeleteGuard {
+    fn issue(&self, id: &str) -> String {
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.retain(|_, (_, issued)| issued.elapsed() < DELETE_TOKEN_TTL);
+        let token = uuid::Uuid::new_v4().to_string();
+        tokens.insert(token.clone(), (id.to_string(), Instant::now()));
+        token
+    }
+
+    fn redeem(&self, token: &str, id: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.remove(token) {
+            Some((task_id, issued)) => task_id == id && issued.elapsed() < DELETE_TOKEN_TTL,
+            None => false,
+        }
+    }
 }
 
 const MIN_PANEL_WIDTH: f64 = 360.0;
 const MAX_PANEL_WIDTH: f64 = 520.0;
+const COMPACT_PANEL_WIDTH: f64 = 96.0;
+
+fn stored_taskbar_offset(app: &AppHandle) -> f64 {
+    app.try_state::<Db>()
+        .and_then(|db| db.taskbar_offset().ok())
+        .unwrap_or(DEFAULT_TASKBAR_OFFSET)
+}
+
+const FALLBACK_PANEL_HEIGHT: f64 = 720.0;
 
-fn configure_main_window(window: &WebviewWindow) -> tauri::Result<()> {
+struct PanelConfigured(AtomicBool);
+
+fn find_monitor(window: &WebviewWindow) -> tauri::Result<Option<Monitor>> {
     if let Some(monitor) = window.current_monitor()? {
-        let scale = monitor.scale_factor();
-        let size = monitor.size();
-        let logical_width = size.width as f64 / scale;
-        let logical_height = size.height as f64 / scale;
-        let target_width = (logical_width * 0.32).clamp(MIN_PANEL_WIDTH, MAX_PANEL_WIDTH);
-        let taskbar_height = 48.0;
-
-        window.set_decorations(false)?;
-        window.set_always_on_top(false)?;
-        window.set_resizable(false)?;
-        window.set_size(LogicalSize::new(target_width, logical_height - taskbar_height))?;
-
-        let position = monitor.position();
-        let offset_x = position.x as f64 / scale;
-        let offset_y = position.y as f64 / scale;
-        window.set_position(LogicalPosition::new(
-            offset_x + logical_width - target_width,
-            offset_y,
-        ))?;
+        return Ok(Some(monitor));
+    }
+    if let Some(monitor) = window.primary_monitor()? {
+        return Ok(Some(monitor));
     }
+    Ok(window.available_monitors()?.into_iter().next())
+}
 
-    Ok(())
+fn configure_main_window(window: &WebviewWindow, taskbar_offset: f64) -> tauri::Result<bool> {
+    window.set_decorations(false)?;
+    window.set_always_on_top(false)?;
+    window.set_resizable(false)?;
+
+    let Some(monitor) = find_monitor(window)? else {
+        window.set_size(LogicalSize::new(MIN_PANEL_WIDTH, FALLBACK_PANEL_HEIGHT))?;
+        return Ok(false);
+    };
+    let scale = monitor.scale_factor();
+    let size = monitor.size();
+    let logical_width = size.width as f64 / scale;
+    let logical_height = size.height as f64 / scale;
+    let target_width = (logical_width * 0.32).clamp(MIN_PANEL_WIDTH, MAX_PANEL_WIDTH);
+
+    window.set_size(LogicalSize::new(
+        target_width,
+        logical_height - taskbar_offset,
+    ))?;
+
+    let position = monitor.position();
+    let offset_x = position.x as f64 / scale;
+    let offset_y = position.y as f64 / scale;
+    window.set_position(LogicalPosition::new(
+        offset_x + logical_width - target_width,
+        offset_y,
+    ))?;
+
+    Ok(true)
+}
+
+fn retry_configure_main_window(app: &AppHandle) {
+    let Some(configured) = app.try_state::<PanelConfigured>() else {
+        return;
+    };
+    if configured.0.load(Ordering::Acquire) {
+        return;
+    }
+    let Some(main) = app.get_webview_window("main") else {
+        return;
+    };
+    match configure_main_window(&main, stored_taskbar_offset(app)) {
+        Ok(done) => configured.0.store(done, Ordering::Release),
+        Err(err) => log::warn!("failed to configure panel: {err}"),
+    }
+}
+
+fn monitor_key(monitor: &Monitor) -> String {
+    match monitor.name() {
+        Some(name) if !name.trim().is_empty() => name.clone(),
+        _ => {
+            let position = monitor.position();
+            let size = monitor.size();
+            format!(
+                "{}x{}@{},{}",
+                size.width, size.height, position.x, position.y
+            )
+        }
+    }
+}
+
+fn save_window_geometry(window: &Window) -> Result<(), String> {
+    let Some(db) = window.try_state::<Db>() else {
+        return Ok(());
+    };
+    if window.is_minimized().map_err(|e| e.to_string())? {
+        return Ok(());
+    }
+    let Some(monitor) = window.current_monitor().map_err(|e| e.to_string())? else {
+        return Ok(());
+    };
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    let geometry = WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    };
+    db.set_window_geometry(&monitor_key(&monitor), &geometry)
+        .map_err(|e| e.to_string())
+}
+
+const GEOMETRY_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Coalesces the stream of move/resize events into one write once the
+/// window has been still for `GEOMETRY_SAVE_DELAY`.
+#[derive(Default)]
+struct GeometrySaver {
+    due: Mutex<Option<Instant>>,
+}
+
+impl GeometrySaver {
+    fn schedule(&self, window: &Window) {
+        let mut due = self.due.lock().unwrap();
+        let pending = due.is_some();
+        *due = Some(Instant::now() + GEOMETRY_SAVE_DELAY);
+        if pending {
+            return;
+        }
+        let window = window.clone();
+        std::thread::spawn(move || {
+            loop {
+                let saver = window.state::<GeometrySaver>();
+                let mut due = saver.due.lock().unwrap();
+                let Some(at) = *due else {
+                    return;
+                };
+                let now = Instant::now();
+                if at <= now {
+                    *due = None;
+                    break;
+                }
+                drop(due);
+                std::thread::sleep(at - now);
+            }
+            if let Err(err) = save_window_geometry(&window) {
+                log::warn!("failed to save window geometry: {err}");
+            }
+        });
+    }
+
+    /// Writes a pending save right away, e.g. before the window closes.
+    fn flush(&self, window: &Window) {
+        if self.due.lock().unwrap().take().is_none() {
+            return;
+        }
+        if let Err(err) = save_window_geometry(window) {
+            log::warn!("failed to save window geometry: {err}");
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PanelGeometry {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    scale_factor: f64,
+    dock_side: &'static str,
+    mode: &'static str,
+    monitor: Option<String>,
+}
+
+#[tauri::command]
+fn get_panel_geometry(app: AppHandle) -> Result<PanelGeometry, ApiError> {
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+
+    let scale = main.scale_factor()?;
+    let position = main.outer_position()?.to_logical::<f64>(scale);
+    let size = main.outer_size()?.to_logical::<f64>(scale);
+    Ok(PanelGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        scale_factor: scale,
+        dock_side: stored_dock_side(&app).as_str(),
+        mode: stored_panel_mode(&app).as_str(),
+        monitor: main.current_monitor()?.as_ref().map(monitor_key),
+    })
+}
+
+/// Set when startup applied a saved geometry, so showing the panel keeps
+/// that placement until something repositions it explicitly.
+struct GeometryRestored(AtomicBool);
+
+fn geometry_restored(app: &AppHandle) -> bool {
+    app.try_state::<GeometryRestored>()
+        .is_some_and(|restored| restored.0.load(Ordering::SeqCst))
+}
+
+fn restore_window_geometry(window: &WebviewWindow, db: &Db) -> tauri::Result<bool> {
+    let Some(monitor) = window.current_monitor()? else {
+        return Ok(false);
+    };
+    let Ok(Some(geometry)) = db.window_geometry(&monitor_key(&monitor)) else {
+        return Ok(false);
+    };
+
+    let origin = monitor.position();
+    let bounds = monitor.size();
+    let fits = geometry.width > 0
+        && geometry.height > 0
+        && geometry.x >= origin.x
+        && geometry.y >= origin.y
+        && i64::from(geometry.x) + i64::from(geometry.width)
+            <= i64::from(origin.x) + i64::from(bounds.width)
+        && i64::from(geometry.y) + i64::from(geometry.height)
+            <= i64::from(origin.y) + i64::from(bounds.height);
+    if !fits {
+        return Ok(false);
+    }
+
+    window.set_size(PhysicalSize::new(geometry.width, geometry.height))?;
+    window.set_position(PhysicalPosition::new(geometry.x, geometry.y))?;
+    Ok(true)
+}
+
+struct PanelBounds {
+    scale: f64,
+    size: LogicalSize<f64>,
+    position: LogicalPosition<f64>,
 }
 
-fn position_main_window(main: &WebviewWindow, side: DockSide) -> Result<(), String> {
-    let monitor = main
-        .current_monitor()
+fn panel_bounds(
+    main: &WebviewWindow,
+    side: DockSide,
+    mode: PanelMode,
+) -> Result<PanelBounds, String> {
+    let monitor = find_monitor(main)
         .map_err(|e| e.to_string())?
         .ok_or_else(|| "monitor information unavailable".to_string())?;
 
@@ -102,7 +1116,10 @@ fn position_main_window(main: &WebviewWindow, side: DockSide) -> Result<(), Stri
     let size = monitor.size();
     let logical_width = size.width as f64 / scale;
     let logical_height = size.height as f64 / scale;
-    let target_width = (logical_width * 0.32).clamp(MIN_PANEL_WIDTH, MAX_PANEL_WIDTH);
+    let target_width = match mode {
+        PanelMode::Compact => COMPACT_PANEL_WIDTH,
+        PanelMode::Full => (logical_width * 0.32).clamp(MIN_PANEL_WIDTH, MAX_PANEL_WIDTH),
+    };
 
     let position = monitor.position();
     let offset_x = position.x as f64 / scale;
@@ -113,94 +1130,526 @@ fn position_main_window(main: &WebviewWindow, side: DockSide) -> Result<(), Stri
         DockSide::Right => offset_x + logical_width - target_width,
     };
 
-    main
-        .set_size(LogicalSize::new(target_width, logical_height))
-        .map_err(|e| e.to_string())?;
-    main
-        .set_position(LogicalPosition::new(x, offset_y))
+    Ok(PanelBounds {
+        scale,
+        size: LogicalSize::new(
+            target_width,
+            logical_height - stored_taskbar_offset(main.app_handle()),
+        ),
+        position: LogicalPosition::new(x, offset_y),
+    })
+}
+
+fn position_main_window(
+    main: &WebviewWindow,
+    side: DockSide,
+    mode: PanelMode,
+) -> Result<(), String> {
+    let bounds = panel_bounds(main, side, mode)?;
+    log::debug!(
+        "positioning panel at {:?} with size {:?}",
+        bounds.position,
+        bounds.size
+    );
+    main.set_size(bounds.size).map_err(|e| e.to_string())?;
+    main.set_position(bounds.position)
         .map_err(|e| e.to_string())?;
+    if let Some(restored) = main.try_state::<GeometryRestored>() {
+        restored.0.store(false, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+fn is_positioned(main: &WebviewWindow, side: DockSide, mode: PanelMode) -> bool {
+    let (Ok(bounds), Ok(size), Ok(position)) = (
+        panel_bounds(main, side, mode),
+        main.inner_size(),
+        main.outer_position(),
+    ) else {
+        return false;
+    };
+    let expected_size = bounds.size.to_physical::<f64>(bounds.scale);
+    let expected_position = bounds.position.to_physical::<f64>(bounds.scale);
+    (size.width as f64 - expected_size.width).abs() <= 1.0
+        && (size.height as f64 - expected_size.height).abs() <= 1.0
+        && (position.x as f64 - expected_position.x).abs() <= 1.0
+        && (position.y as f64 - expected_position.y).abs() <= 1.0
+}
+
+#[tauri::command]
+fn reveal_panel(
+    app: AppHandle,
+    db: State<Db>,
+    debounce: State<PanelDebounce>,
+    side: DockSide,
+) -> Result<(), ApiError> {
+    if !debounce.ready("reveal") || presenting(&app) {
+        return Ok(());
+    }
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+
+    let visible = main.is_visible()?;
+    let side_changed = stored_dock_side(&app) != side;
+    let mode = stored_panel_mode(&app);
+    let misplaced = !visible || !is_positioned(&main, side, mode);
+    if side_changed || (misplaced && !geometry_restored(&app)) {
+        position_main_window(&main, side, mode).map_err(ApiError::Window)?;
+    }
+    if side_changed || db.dock_side()?.is_none() {
+        db.set_dock_side(side.as_str())?;
+    }
+    if !visible {
+        #[cfg(target_os = "macos")]
+        app.show()?;
+        main.show()?;
+    }
+    let _ = main.set_focus();
+    if side_changed || !visible {
+        let _ = main.emit("time-master::dock", side.as_str());
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+fn request_dock_state(app: AppHandle, db: State<Db>) -> Result<(), ApiError> {
+    let side = db
+        .dock_side()?
+        .and_then(|side| DockSide::from_setting(&side))
+        .ok_or_else(|| ApiError::NotFound("no dock side has been stored yet".into()))?;
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+    main.emit("time-master::dock", side.as_str())?;
+    Ok(())
+}
+
+fn realign_main_window(app: &AppHandle) -> Result<(), String> {
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| "main window unavailable".to_string())?;
+    position_main_window(&main, stored_dock_side(app), stored_panel_mode(app))
+}
+
+#[tauri::command]
+fn realign_panel(app: AppHandle) -> Result<(), ApiError> {
+    realign_main_window(&app).map_err(ApiError::Window)
+}
+
+#[tauri::command]
+fn set_panel_mode(app: AppHandle, db: State<Db>, mode: String) -> Result<(), ApiError> {
+    let mode = PanelMode::from_setting(mode.trim()).ok_or_else(|| {
+        ApiError::InvalidInput(format!("panel mode must be compact or full, got {mode}"))
+    })?;
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+
+    position_main_window(&main, stored_dock_side(&app), mode).map_err(ApiError::Window)?;
+    db.set_panel_mode(mode.as_str())?;
+    let _ = main.emit("time-master::panel-mode", mode.as_str());
+
     Ok(())
 }
 
 #[tauri::command]
-fn reveal_panel(app: AppHandle, side: DockSide) -> Result<(), String> {
+fn set_taskbar_offset(app: AppHandle, db: State<Db>, pixels: f64) -> Result<(), ApiError> {
+    db.set_taskbar_offset(pixels)?;
+    realign_main_window(&app).map_err(ApiError::Window)
+}
+
+fn toggle_main_window(app: &AppHandle) -> Result<(), String> {
     let main = app
         .get_webview_window("main")
         .ok_or_else(|| "main window unavailable".to_string())?;
 
-    position_main_window(&main, side)?;
+    if main.is_visible().map_err(|e| e.to_string())? {
+        return hide_main_window(&main).map_err(|e| e.to_string());
+    }
+    show_main_window(app, &main)
+}
+
+// Hiding the only window leaves no key window on macOS, so the app is hidden
+// too, which hands focus back to whichever application was active before.
+fn hide_main_window(main: &WebviewWindow) -> tauri::Result<()> {
+    main.hide()?;
+    #[cfg(target_os = "macos")]
+    main.app_handle().hide()?;
+    Ok(())
+}
+
+fn show_main_window(app: &AppHandle, main: &WebviewWindow) -> Result<(), String> {
+    if presenting(app) {
+        return Ok(());
+    }
+    if !geometry_restored(app) {
+        position_main_window(main, stored_dock_side(app), stored_panel_mode(app))?;
+    }
+    #[cfg(target_os = "macos")]
+    app.show().map_err(|e| e.to_string())?;
     main.show().map_err(|e| e.to_string())?;
     let _ = main.set_focus();
-    let _ = main.emit("time-master::dock", side.as_str());
+    Ok(())
+}
+
+fn quick_add(app: &AppHandle) -> Result<(), String> {
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| "main window unavailable".to_string())?;
+
+    show_main_window(app, &main)?;
+    main.emit("time-master::quick-add", ())
+        .map_err(|e| e.to_string())
+}
+
+fn register_shortcut(
+    app: &AppHandle,
+    accelerator: &str,
+    action: &'static str,
+    handler: fn(&AppHandle) -> Result<(), String>,
+) {
+    let result = app
+        .global_shortcut()
+        .on_shortcut(accelerator, move |handle, _shortcut, _| {
+            if !handle.state::<PanelDebounce>().ready(action) {
+                return;
+            }
+            log::debug!("{action} shortcut pressed");
+
+            if let Err(err) = handler(handle) {
+                log::error!("failed to run {action} shortcut: {err}");
+            }
+        });
+    match result {
+        Ok(()) => log::info!("registered {action} shortcut {accelerator}"),
+        Err(err) => log::error!("failed to register {action} shortcut {accelerator}: {err}"),
+    }
+}
 
+fn same_shortcut(a: &str, b: &str) -> bool {
+    match (a.parse::<Shortcut>(), b.parse::<Shortcut>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.trim().eq_ignore_ascii_case(b.trim()),
+    }
+}
+
+#[tauri::command]
+fn toggle_panel(app: AppHandle, debounce: State<PanelDebounce>) -> Result<(), ApiError> {
+    if !debounce.ready("toggle") {
+        return Ok(());
+    }
+    toggle_main_window(&app).map_err(ApiError::Window)
+}
+
+#[tauri::command]
+fn conceal_panel(app: AppHandle, debounce: State<PanelDebounce>) -> Result<(), ApiError> {
+    if !debounce.ready("conceal") {
+        return Ok(());
+    }
+    let main = app
+        .get_webview_window("main")
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+    hide_main_window(&main)?;
     Ok(())
 }
 
+#[derive(Clone, Copy)]
+struct PanelSnapshot {
+    visible: bool,
+    always_on_top: bool,
+}
+
+#[derive(Default)]
+struct PresentationMode {
+    saved: Mutex<Option<PanelSnapshot>>,
+}
+
+fn presenting(app: &AppHandle) -> bool {
+    app.try_state::<PresentationMode>()
+        .is_some_and(|mode| mode.saved.lock().unwrap().is_some())
+}
+
 #[tauri::command]
-fn conceal_panel(app: AppHandle) -> Result<(), String> {
+fn presentation_mode(
+    app: AppHandle,
+    mode: State<PresentationMode>,
+    on: bool,
+) -> Result<(), ApiError> {
     let main = app
         .get_webview_window("main")
-        .ok_or_else(|| "main window unavailable".to_string())?;
-    let _ = main;
+        .ok_or_else(|| ApiError::Window("main window unavailable".into()))?;
+    let mut saved = mode.saved.lock().unwrap();
+
+    if on {
+        if saved.is_none() {
+            *saved = Some(PanelSnapshot {
+                visible: main.is_visible()?,
+                always_on_top: main.is_always_on_top()?,
+            });
+        }
+        main.set_always_on_top(false)?;
+        main.hide()?;
+    } else if let Some(snapshot) = saved.take() {
+        main.set_always_on_top(snapshot.always_on_top)?;
+        if snapshot.visible {
+            main.show()?;
+        }
+    }
+    drop(saved);
+
+    let _ = app.emit("time-master::presentation-mode", on);
+    Ok(())
+}
+
+const TRAY_ID: &str = "main";
+const TRAY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+fn refresh_tray_tooltip(app: &AppHandle) {
+    let (Some(tray), Some(db)) = (app.tray_by_id(TRAY_ID), app.try_state::<Db>()) else {
+        return;
+    };
+    match db.count_active_tasks() {
+        Ok(active) => {
+            let _ = tray.set_tooltip(Some(format!("TimeMaster: {active} active tasks")));
+        }
+        Err(err) => log::warn!("failed to count active tasks: {err}"),
+    }
+}
+
+fn build_tray(app: &AppHandle) -> tauri::Result<()> {
+    let toggle = MenuItem::with_id(app, "toggle", "Show/Hide", true, None::<&str>)?;
+    let add = MenuItem::with_id(app, "add", "Add task", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&toggle, &add, &quit])?;
+
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip("TimeMaster")
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            "toggle" => {
+                if let Err(err) = toggle_main_window(app) {
+                    log::error!("failed to toggle panel: {err}");
+                }
+            }
+            "add" => {
+                if !app.state::<PanelDebounce>().ready("quick_add") {
+                    return;
+                }
+                if let Err(err) = quick_add(app) {
+                    log::error!("failed to open quick add: {err}");
+                }
+            }
+            "quit" => app.exit(0),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                let app = tray.app_handle();
+                if app.state::<PanelDebounce>().ready("toggle") {
+                    if let Err(err) = toggle_main_window(app) {
+                        log::error!("failed to toggle panel: {err}");
+                    }
+                }
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    refresh_tray_tooltip(app);
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TRAY_REFRESH_INTERVAL);
+        refresh_tray_tooltip(&handle);
+    });
     Ok(())
 }
 
+fn open_database(app: &AppHandle) -> Result<Db, DbError> {
+    let err = match Db::init(app) {
+        Ok(db) => return Ok(db),
+        Err(err) => err,
+    };
+    log::error!("failed to open database, falling back to temporary storage: {err}");
+    let db = Db::init_fallback().or_else(|_| Db::init_in_memory())?;
+
+    let message = format!(
+        "{err}\n\nTimeMaster is running with temporary storage; changes may be lost when it closes."
+    );
+    let _ = app.emit("time-master::storage-degraded", &message);
+    app.dialog()
+        .message(message)
+        .title("TimeMaster storage unavailable")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+    Ok(db)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
     tauri::Builder::default()
+        .manage(PanelDebounce::default())
+        .manage(GeometrySaver::default())
+        .manage(DeleteGuard::default())
+        .manage(PresentationMode::default())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             greet,
             list_tasks,
+            delta_tasks,
+            list_grouped,
+            search_tasks,
+            list_tasks_in_range,
+            list_changed_since,
+            completed_on,
+            week_agenda,
+            query_tasks,
+            get_today,
+            next_task,
+            validate_task,
             create_task,
+            create_tasks,
             update_task,
+            patch_task,
+            rename_task,
+            change_task_type,
+            clear_schedule,
+            set_task_tags,
+            rename_tag,
+            set_priority_bulk,
+            set_parent,
+            list_tree,
+            find_orphans,
+            resolve_orphans,
+            merge_tasks,
+            request_delete,
             delete_task,
             increase_task_progress,
+            adjust_target,
+            complete_task,
+            toggle_complete,
+            snooze_task,
+            freeze_task,
+            start_timer,
+            stop_timer,
+            total_time_spent,
+            set_reminder_time,
+            run_query,
+            daily_caps,
+            set_daily_cap,
             archive_task,
+            archive_task_cascade,
+            list_stale,
+            archive_matching,
+            auto_archive_completed,
             reopen_task,
+            resetting_today,
+            reset_cycle,
+            upcoming_deadlines,
+            find_invalid_ranges,
+            progress_timeline,
+            habit_age,
+            project_completion,
+            completion_history,
+            creation_histogram,
+            velocity_trend,
+            list_task_types,
+            group_progress,
+            find_duplicates,
+            weekly_review,
+            top_streaks,
+            export_ics,
+            export_report,
+            export_tasks,
+            export_tasks_ndjson,
+            diff_against_export,
+            export_task,
+            import_task,
+            import_tasks_csv,
+            get_schema_version,
+            compact_database,
+            set_data_dir,
+            get_profile,
+            switch_profile,
+            backfill_streaks,
+            reconcile_imported,
+            checkpoint_wal,
+            db_stats,
+            get_dnd,
+            set_dnd,
+            clear_dnd,
+            get_setting,
+            set_log_level,
+            set_setting,
             reveal_panel,
+            request_dock_state,
+            realign_panel,
+            get_panel_geometry,
+            set_panel_mode,
+            set_taskbar_offset,
+            toggle_panel,
+            presentation_mode,
             conceal_panel
         ])
+        .on_window_event(|window, event| {
+            if window.label() != "main" {
+                return;
+            }
+            match event {
+                WindowEvent::ScaleFactorChanged { .. } => {
+                    if let Err(err) = realign_main_window(window.app_handle()) {
+                        log::warn!("failed to realign panel: {err}");
+                    }
+                }
+                WindowEvent::Focused(true) => retry_configure_main_window(window.app_handle()),
+                WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+                    window.state::<GeometrySaver>().schedule(window)
+                }
+                WindowEvent::CloseRequested { .. } | WindowEvent::Destroyed => {
+                    window.state::<GeometrySaver>().flush(window)
+                }
+                _ => {}
+            }
+        })
         .setup(|app| {
             let app_handle = app.handle();
-            let db = Db::init(&app_handle)?;
-            app.manage(db);
+            let db = open_database(app_handle)?;
+            logging::apply_setting(db.log_level()?.as_deref());
+            let toggle_shortcut = db.toggle_shortcut()?;
+            let quick_add_shortcut = db.quick_add_shortcut()?;
 
             let main_window = app_handle
                 .get_webview_window("main")
                 .expect("main window missing");
-            configure_main_window(&main_window)?;
-
-            let last_trigger = Arc::new(Mutex::new(Instant::now()));
-            let global_shortcut = app_handle.global_shortcut();
-            let last_trigger_clone = Arc::clone(&last_trigger);
-
-            global_shortcut.on_shortcut("Alt+Q", move |handle, _shortcut, _| {
-                let mut last = last_trigger_clone.lock().unwrap();
-                let now = Instant::now();
-                if now.duration_since(*last) < Duration::from_millis(200) {
-                    return;
-                }
-                *last = now;
+            let configured = configure_main_window(&main_window, db.taskbar_offset()?)?;
+            app.manage(PanelConfigured(AtomicBool::new(configured)));
+            let restored = restore_window_geometry(&main_window, &db)?;
+            app.manage(GeometryRestored(AtomicBool::new(restored)));
+            app.manage(db);
+            spawn_wal_checkpoints(app_handle);
+            spawn_reminders(app_handle);
+            build_tray(app_handle)?;
 
-                if let Some(window) = handle.get_webview_window("main") {
-                    match window.is_visible() {
-                        Ok(true) => {
-                            let _ = window.hide();
-                        }
-                        Ok(false) => {
-                            if position_main_window(&window, DockSide::Right).is_ok() {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
-                        }
-                        Err(err) => {
-                            eprintln!("failed to read window visibility: {err}");
-                        }
-                    }
-                }
-            })?;
+            register_shortcut(app_handle, &toggle_shortcut, "toggle", toggle_main_window);
+            if same_shortcut(&toggle_shortcut, &quick_add_shortcut) {
+                log::warn!(
+                    "quick add shortcut {quick_add_shortcut} is already used to toggle the panel"
+                );
+            } else {
+                register_shortcut(app_handle, &quick_add_shortcut, "quick_add", quick_add);
+            }
 
             Ok(())
         })
@@ -0,0 +1,45 @@
+﻿use log::{LevelFilter, Log, Metadata, Record};
+
+const LOG_ENV: &str = "RUST_LOG";
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(env_level().unwrap_or(DEFAULT_LEVEL));
+    }
+}
+
+// RUST_LOG always wins so a field build can be made verbose without touching
+// the stored setting.
+pub fn apply_setting(value: Option<&str>) {
+    if env_level().is_some() {
+        return;
+    }
+    log::set_max_level(value.and_then(parse_level).unwrap_or(DEFAULT_LEVEL));
+}
+
+pub fn parse_level(value: &str) -> Option<LevelFilter> {
+    value.trim().parse().ok()
+}
+
+fn env_level() -> Option<LevelFilter> {
+    std::env::var(LOG_ENV).ok().as_deref().and_then(parse_level)
+}
@@ -42,6 +42,15 @@ const normalizeTask = (payload) => ({
   startDate: payload.startDate ?? null,
   endDate: payload.endDate ?? null,
   status: payload.status ?? "active",
+  color: payload.color ?? null,
+  snoozedUntil: payload.snoozedUntil ?? null,
+  frozenUntil: payload.frozenUntil ?? null,
+  reminderTime: payload.reminderTime ?? null,
+  regenerateRule: payload.regenerateRule ?? null,
+  notes: payload.notes ?? null,
+  parentId: payload.parentId ?? null,
+  priority: payload.priority ?? 0,
+  tags: payload.tags ?? [],
   createdAt: payload.createdAt ?? new Date().toISOString(),
   updatedAt: payload.updatedAt ?? new Date().toISOString()
 })
@@ -82,7 +91,7 @@ const refreshTasks = async () => {
   try {
     loading.value = true
     lastError.value = null
-    const response = await invoke("list_tasks", { status: null })
+    const response = await invoke("list_tasks", { statuses: null })
     tasks.value = Array.isArray(response) ? response.map(normalizeTask) : []
     sortTasks()
     loaded.value = true
@@ -131,7 +140,11 @@ const addTask = async (payload) => {
       type: payload.type,
       target: payload.target ?? 1,
       repeat: payload.repeat ?? null,
-      dateRange: payload.dateRange ?? null
+      dateRange: payload.dateRange ?? null,
+      color: payload.color ?? null,
+      notes: payload.notes ?? null,
+      regenerate: payload.regenerate ?? null,
+      reminderTime: payload.reminderTime ?? null
     }
   })
   await refreshTasks()
@@ -157,7 +170,11 @@ const updateTask = async (payload) => {
       type: payload.type,
       target: payload.target ?? null,
       repeat: payload.repeat ?? null,
-      dateRange: payload.dateRange ?? null
+      dateRange: payload.dateRange ?? null,
+      color: payload.color ?? null,
+      notes: payload.notes ?? null,
+      regenerate: payload.regenerate ?? null,
+      reminderTime: payload.reminderTime ?? null
     }
   })
   await refreshTasks()
@@ -169,7 +186,8 @@ const deleteTask = async (id) => {
     tasks.value = tasks.value.filter(task => task.id !== id)
     return
   }
-  await invoke("delete_task", { payload: { id } })
+  const token = await invoke("request_delete", { payload: { id } })
+  await invoke("delete_task", { payload: { id, token } })
   await refreshTasks()
 }
 